@@ -1,16 +1,24 @@
+extern crate getopts;
 extern crate starplayer;
 
+use std::env;
 use std::time::SystemTime;
 
+use getopts::Options;
+
 use starplayer::StarAI;
+use starplayer::config::{GameConfig, SearchConfig, parse_opt};
+use starplayer::patterns::PatternLibrary;
 
 const SIZE: usize = 5;
-const LOOP_ITERATIONS: usize = 1;
+// Large enough that the cost of starting threads in each call to calculate is insignificant
+const LOOP_ITERATIONS: usize = 64;
 const KOMI: isize = 1;
 const TRIAL_TIME: u64 = 10;
 const TRIALS: usize = 10;
+const THREAD_COUNTS: [usize; 3] = [1, 2, 4];
 
-fn print_statistics(iteration_records: Vec<usize>) {
+fn print_statistics(iteration_records: Vec<usize>, trials: usize) {
     let mut mean = 0.0;
     for record in iteration_records.iter() {
         mean += *record as f64;
@@ -20,25 +28,58 @@ fn print_statistics(iteration_records: Vec<usize>) {
     for record in iteration_records.iter() {
         stddev += (mean - *record as f64).powi(2);
     }
-    stddev = (stddev / iteration_records.len() as f64).powf(0.5) / (TRIALS as f64).sqrt();
+    stddev = (stddev / iteration_records.len() as f64).powf(0.5) / (trials as f64).sqrt();
     println!("{} +- {}", mean, stddev);
 }
 
 fn main() {
-    let mut iteration_records = Vec::new();
-    for _ in 0..TRIALS {
-        let mut ai = StarAI::new(SIZE);
-        let start_time = SystemTime::now();
-        let mut iterations = 0;
-        loop {
-            ai.calculate(LOOP_ITERATIONS, KOMI);
-            iterations += LOOP_ITERATIONS;
-            if start_time.elapsed().unwrap().as_secs() >= TRIAL_TIME {
-                break;
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("", "trial-time", &format!("seconds to run each trial for (default {})", TRIAL_TIME), "SECONDS");
+    opts.optopt("", "trials", &format!("number of trials for each thread count (default {})", TRIALS), "N");
+    GameConfig::add_options(&mut opts);
+    SearchConfig::add_options(&mut opts);
+    PatternLibrary::add_options(&mut opts);
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => { m },
+        Err(f) => { panic!("{}", f) },
+    };
+    let config = GameConfig::from_matches(&matches, GameConfig::new(SIZE, KOMI)).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let search_config = SearchConfig::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let patterns = PatternLibrary::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let trial_time = parse_opt(&matches, "trial-time", TRIAL_TIME).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let trials = parse_opt(&matches, "trials", TRIALS).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+
+    for threads in THREAD_COUNTS.iter() {
+        println!("Threads: {}", threads);
+        let mut iteration_records = Vec::new();
+        for _ in 0..trials {
+            let mut ai = StarAI::with_search_config(config, search_config);
+            ai.set_patterns(patterns.clone());
+            ai.set_threads(*threads);
+            let start_time = SystemTime::now();
+            let mut iterations = 0;
+            loop {
+                ai.calculate(LOOP_ITERATIONS);
+                iterations += LOOP_ITERATIONS;
+                if start_time.elapsed().unwrap().as_secs() >= trial_time {
+                    break;
+                }
             }
+            println!("Iterations: {}", iterations);
+            iteration_records.push(iterations);
         }
-        println!("Iterations: {}", iterations);
-        iteration_records.push(iterations);
+        print_statistics(iteration_records, trials);
     }
-    print_statistics(iteration_records);
 }
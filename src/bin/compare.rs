@@ -5,12 +5,14 @@ use std::process::{Command, Stdio, ChildStdin, ChildStdout};
 use std::env;
 use std::path;
 use std::io::{Write, BufReader, BufRead};
-use std::str::FromStr;
 
 use getopts::Options;
 
 use starplayer::player::Player;
+use starplayer::protocol::{format_player, parse_point, format_point};
 use starplayer::StarAI;
+use starplayer::config::GameConfig;
+use starplayer::record::GameRecord;
 
 const SIZE: usize = 7;
 const KOMI: isize = 1;
@@ -18,8 +20,14 @@ const KOMI: isize = 1;
 const DIR_A: &'static str = "test/a";
 const DIR_B: &'static str = "test/b";
 
-fn print_usage(program: &str) {
-    println!("Usage: {} REV1 REV2", program);
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!(
+        "Usage: {} [options] REV1 REV2\n\n\
+         Both revisions must have a player speaking the GTP-style protocol, so revisions from\n\
+         before it was introduced can't be compared.",
+        program,
+    );
+    println!("{}", opts.usage(&brief));
 }
 
 fn checkout(dest: &str) {
@@ -75,8 +83,10 @@ impl PlayerIO {
         compile(dir, rev);
     }
 
-    fn new(dir: &str) -> PlayerIO {
+    /// Start the player built in `dir`, passing it the given command line arguments
+    fn new(dir: &str, args: &[&str]) -> PlayerIO {
         let child = Command::new(dir.to_string() + "/target/release/player")
+            .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()
@@ -89,18 +99,53 @@ impl PlayerIO {
         }
     }
 
-    fn read<F: FromStr>(&mut self) -> F {
-        let mut buffer = String::new();
-        self.stdout.read_line(&mut buffer).unwrap();
-        buffer.trim().parse::<F>().unwrap_or_else(|_| {
-            panic!("Failed to parse read value");
+    /// Send a command to the player, returning the response, or the error message on failure
+    fn command(&mut self, command: &str) -> Result<String, String> {
+        self.stdin.write_all(command.as_bytes()).unwrap();
+        self.stdin.write_all(b"\n").unwrap();
+        self.stdin.flush().unwrap();
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).unwrap() == 0 {
+                panic!("Player exited while running '{}'", command);
+            }
+            if line.trim().is_empty() && !response.is_empty() {
+                break;
+            }
+            response.push_str(&line);
+        }
+        let result = response[1..].trim().to_string();
+        if response.starts_with('=') {
+            Ok(result)
+        } else {
+            Err(result)
+        }
+    }
+
+    fn command_or_panic(&mut self, command: &str) -> String {
+        self.command(command).unwrap_or_else(|e| {
+            panic!("Command '{}' failed: {}", command, e);
         })
     }
 
-    fn write<F: ToString>(&mut self, value: F) {
-        self.stdin.write(value.to_string().as_bytes()).unwrap();
-        self.stdin.write(b"\n").unwrap();
-        self.stdin.flush().unwrap();
+    /// Start a new game, with the given moves already played
+    fn new_game(&mut self, config: GameConfig, moves: &[(usize, usize)]) {
+        self.command_or_panic(&format!("boardsize {}", config.size));
+        self.command_or_panic(&format!("komi {}", config.komi));
+        for (i, &(x, y)) in moves.iter().enumerate() {
+            let player = if i%2 == 0 { Player::First } else { Player::Second };
+            self.command_or_panic(&format!("play {} {}", format_player(player), format_point((x, y))));
+        }
+    }
+}
+
+/// Name of a player in game records, identifying the revision and arguments it was run with
+fn record_name(label: &str, rev: &str, args: &str) -> String {
+    if args.is_empty() {
+        format!("{} ({})", label, rev)
+    } else {
+        format!("{} ({} {})", label, rev, args)
     }
 }
 
@@ -108,55 +153,103 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
 
-    let opts = Options::new();
+    let mut opts = Options::new();
+    opts.optopt("r", "records", "write a record of each game to DIR", "DIR");
+    opts.optopt("l", "load", "start each game from the position recorded in FILE", "FILE");
+    opts.optopt("a", "args-a", "command line arguments for player A, e.g. search parameters", "ARGS");
+    opts.optopt("b", "args-b", "command line arguments for player B", "ARGS");
+    GameConfig::add_options(&mut opts);
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m },
-        Err(f) => { panic!(f.to_string()) },
+        Err(f) => { panic!("{}", f) },
     };
+    let config = GameConfig::from_matches(&matches, GameConfig::new(SIZE, KOMI)).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let args_a = matches.opt_str("a").unwrap_or_default();
+    let args_b = matches.opt_str("b").unwrap_or_default();
 
     if matches.free.len() != 2 {
-        print_usage(&program);
+        print_usage(&program, &opts);
         return;
     }
 
+    let records_dir = matches.opt_str("r");
+    let loaded = matches.opt_str("l").map(|path| {
+        GameRecord::load(&path).unwrap_or_else(|e| {
+            panic!("Failed to load record {}: {}", path, e);
+        })
+    });
+
     let mut a_wins = 0;
     let mut b_wins = 0;
 
     PlayerIO::prepare(DIR_A, &matches.free[0]);
     PlayerIO::prepare(DIR_B, &matches.free[1]);
+    let name_a = record_name("A", &matches.free[0], &args_a);
+    let name_b = record_name("B", &matches.free[1], &args_b);
+
+    let mut pa = PlayerIO::new(DIR_A, &args_a.split_whitespace().collect::<Vec<_>>());
+    let mut pb = PlayerIO::new(DIR_B, &args_b.split_whitespace().collect::<Vec<_>>());
 
     loop {
         let a_first_player = (a_wins + b_wins)%2 == 0;
-        let mut ai = StarAI::new(SIZE);
-        let mut pa = PlayerIO::new(DIR_A);
-        let mut pb = PlayerIO::new(DIR_B);
-        if a_first_player {
-            pa.write(0);
-            pb.write(1);
-        } else {
-            pb.write(0);
-            pa.write(1);
-        }
-        while !ai.finished(KOMI) {
-            // TODO: refactoring
-            let (x, y) = if a_first_player == (ai.player_turn() == Player::First) {
-                let x = pa.read();
-                let y = pa.read();
-                pb.write(x);
-                pb.write(y);
-                (x, y)
+        let mut ai = match loaded {
+            None => StarAI::new(config),
+            Some(ref record) => record.replay().unwrap_or_else(|e| {
+                panic!("Failed to replay record: {}", e);
+            }),
+        };
+        pa.new_game(ai.config(), &ai.history());
+        pb.new_game(ai.config(), &ai.history());
+        let a_won;
+        loop {
+            let player = ai.player_turn();
+            let a_to_move = a_first_player == (player == Player::First);
+            let (mover, other) = if a_to_move {
+                (&mut pa, &mut pb)
             } else {
-                let x = pb.read();
-                let y = pb.read();
-                pa.write(x);
-                pa.write(y);
-                (x, y)
+                (&mut pb, &mut pa)
+            };
+            let response = mover.command(&format!("genmove {}", format_player(player)));
+            // An illegal move or a failure to generate one forfeits the game
+            let result = match response {
+                Err(e) => Err(e),
+                Ok(point) => match parse_point(&point) {
+                    Err(e) => Err(e),
+                    Ok((x, y)) => ai.add_move(x, y).map(|_| (x, y)).map_err(|e| {
+                        format!("Illegal move {} {}: {}", x, y, e)
+                    }),
+                },
             };
-            ai.add_move(x, y);
+            let (x, y) = match result {
+                Err(e) => {
+                    println!("{} forfeits: {}", if a_to_move { "A" } else { "B" }, e);
+                    a_won = !a_to_move;
+                    break;
+                },
+                Ok(point) => point,
+            };
+            other.command_or_panic(&format!("play {} {}", format_player(player), format_point((x, y))));
             ai.print_board();
             println!("Stats: {} - {}", a_wins, b_wins);
+            if let Some(winner) = ai.winner() {
+                a_won = a_first_player == (winner == Player::First);
+                break;
+            }
+        }
+        if let Some(ref dir) = records_dir {
+            let (first, second) = if a_first_player {
+                (&name_a, &name_b)
+            } else {
+                (&name_b, &name_a)
+            };
+            let path = path::Path::new(dir).join(format!("game-{}.txt", a_wins + b_wins + 1));
+            GameRecord::from_ai(&ai, first, second).save(&path).unwrap_or_else(|e| {
+                panic!("Failed to write record {}: {}", path.display(), e);
+            });
         }
-        if a_first_player == (ai.winner(KOMI).unwrap() == Player::First) {
+        if a_won {
             a_wins += 1;
         } else {
             b_wins += 1;
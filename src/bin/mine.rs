@@ -0,0 +1,116 @@
+extern crate getopts;
+extern crate starplayer;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use getopts::Options;
+
+use starplayer::StarAI;
+use starplayer::config::{GameConfig, SearchConfig, add_thread_options, threads_from_matches, parse_opt};
+use starplayer::mining::PatternMiner;
+use starplayer::patterns::PatternLibrary;
+use starplayer::record::GameRecord;
+
+const SIZE: usize = 5;
+const KOMI: isize = 1;
+/// Number of self-play games to mine
+const GAMES: u64 = 100;
+/// Iterations searched for each move of the self-play games
+const ITERATIONS: usize = 1000;
+/// Number of times an answer must have been played to be written out
+const MIN_COUNT: u32 = 10;
+
+fn print_usage(program: &str, opts: &Options) {
+    println!("{}", opts.usage(&format!("Usage: {} [options] OUTPUT", program)));
+}
+
+/// Play a game against itself, searching `iterations` iterations for each move
+fn self_play(
+    config: GameConfig,
+    search_config: SearchConfig,
+    patterns: &PatternLibrary,
+    threads: usize,
+    iterations: usize,
+) -> GameRecord {
+    let mut ai = StarAI::with_search_config(config, search_config);
+    ai.set_patterns(patterns.clone());
+    ai.set_threads(threads);
+    while !ai.finished() {
+        ai.calculate(iterations);
+        let (x, y) = ai.best_move();
+        ai.add_move(x, y).unwrap();
+    }
+    GameRecord::from_ai(&ai, "self-play", "self-play")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt("", "games", &format!("number of self-play games (default {})", GAMES), "N");
+    opts.optopt("", "iterations", &format!("iterations searched for each move (default {})", ITERATIONS), "N");
+    opts.optopt("", "min-count", &format!("times an answer must be played to be written out (default {})", MIN_COUNT), "N");
+    GameConfig::add_options(&mut opts);
+    SearchConfig::add_options(&mut opts);
+    add_thread_options(&mut opts);
+    PatternLibrary::add_options(&mut opts);
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => { m },
+        Err(f) => { panic!("{}", f) },
+    };
+    if matches.free.len() != 1 {
+        print_usage(&program, &opts);
+        return;
+    }
+    let config = GameConfig::from_matches(&matches, GameConfig::new(SIZE, KOMI)).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let search_config = SearchConfig::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let patterns = PatternLibrary::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let threads = threads_from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let games = parse_opt(&matches, "games", GAMES).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let iterations = parse_opt(&matches, "iterations", ITERATIONS).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let min_count = parse_opt(&matches, "min-count", MIN_COUNT).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+
+    let mut miner = PatternMiner::new();
+    for game in 0..games {
+        // A seeded search would otherwise play the same game every time
+        let mut game_search_config = search_config;
+        game_search_config.seed = search_config.seed.map(|seed| seed.wrapping_add(game));
+        let record = self_play(config, game_search_config, &patterns, threads, iterations);
+        println!("Game {}: {} moves, {:?} wins", game + 1, record.moves.len(), record.result.unwrap().winner);
+        miner.add_game(&record);
+    }
+
+    let mined = miner.patterns(min_count);
+    let mut output = format!("# Mined from {} self-play games on a board of size {}\n", games, config.size);
+    for mined_pattern in mined.iter() {
+        output.push_str(&format!(
+            "\n# Played {} of {} times, winning {:.1}%\n{}",
+            mined_pattern.count,
+            mined_pattern.occurrences,
+            mined_pattern.winrate() * 100.,
+            mined_pattern.pattern,
+        ));
+    }
+    let path = &matches.free[0];
+    File::create(path).and_then(|mut file| file.write_all(output.as_bytes())).unwrap_or_else(|e| {
+        panic!("Failed to write patterns {}: {}", path, e);
+    });
+    println!("Wrote {} patterns to {}", mined.len(), path);
+}
@@ -1,27 +1,70 @@
+extern crate getopts;
 extern crate starplayer;
 extern crate time;
 
 const SIZE: usize = 5;
 const LOOP_ITERATIONS: usize = 64;
 const KOMI: isize = 1;
-const MOVE_TIME: u64 = 30;
+/// Main time for the computer's moves, in seconds
+const MAIN_TIME: u64 = 600;
+/// Time added to the computer's clock after each of its moves, in seconds
+const INCREMENT: u64 = 10;
+/// Number of candidate moves shown after the computer moves
+const ANALYSIS_MOVES: usize = 5;
 
+use std::env;
 use std::io;
-use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
-use starplayer::StarAI;
-use starplayer::player::PLAYERS;
+use getopts::Options;
 
-fn make_move(ai: &mut StarAI) {
-    let start_time = SystemTime::now();
-    loop {
-        ai.calculate(LOOP_ITERATIONS, KOMI);
-        if start_time.elapsed().unwrap().as_secs() >= MOVE_TIME {
-            break;
-        }
-    }
+use starplayer::{StarAI, MoveError, BYTES_PER_MEGABYTE};
+use starplayer::config::{GameConfig, SearchConfig, add_thread_options, threads_from_matches};
+use starplayer::patterns::PatternLibrary;
+use starplayer::player::{Player, PLAYERS};
+use starplayer::record::GameRecord;
+use starplayer::time_control::{self, Clock, TimeControl};
+
+/// Think of and play the computer's move, then ponder until the player's move if `ponder` is set
+fn make_move(ai: &mut StarAI, clock: &mut Clock, ponder: bool) {
+    let start_time = Instant::now();
+    ai.stop_pondering();
+    let budget = clock.budget(ai.empty_cells());
+    time_control::think(ai, &budget, LOOP_ITERATIONS);
+    print_analysis(ai);
     let (x, y) = ai.best_move();
-    ai.add_move(x, y);
+    ai.add_move(x, y).unwrap();
+    clock.record_move(start_time.elapsed());
+    if ponder {
+        ai.start_pondering();
+    }
+}
+
+/// Show the moves the computer considered, using the same one based coordinates as for input
+fn print_analysis(ai: &StarAI) {
+    for analysis in ai.analysis(ANALYSIS_MOVES) {
+        let winrate = match analysis.proven_winner {
+            Some(winner) if winner == ai.player_turn() => "won".to_string(),
+            Some(_) => "lost".to_string(),
+            None => analysis.winrate.map_or("-".to_string(), |winrate| format!("{:.1}%", winrate * 100.)),
+        };
+        let variation = analysis.variation.iter().map(|&(x, y)| format!("{} {}", x + 1, y + 1)).collect::<Vec<_>>();
+        println!(
+            "{:>5} visits {:>7} amaf {:>7} winrate {:>6}  {}",
+            format!("{} {}", analysis.point.0 + 1, analysis.point.1 + 1),
+            analysis.visits,
+            analysis.amaf_visits,
+            winrate,
+            variation.join(", "),
+        );
+    }
+    let usage = ai.table_usage();
+    println!(
+        "Tree: {} nodes, {:.1} MB, {} evicted",
+        usage.nodes,
+        usage.memory as f64 / BYTES_PER_MEGABYTE as f64,
+        usage.evicted,
+    );
 }
 
 fn read_line() -> io::Result<String> {
@@ -34,35 +77,149 @@ fn read_line() -> io::Result<String> {
 
 fn print_scores(ai: &StarAI) {
     for player in PLAYERS.iter() {
-        println!("{:?}: {}", player, ai.score(*player, KOMI));
+        println!("{:?}: {}", player, ai.score(*player));
+    }
+}
+
+/// Names of the first and second players in game records
+fn player_names(is_first_player: bool) -> (&'static str, &'static str) {
+    if is_first_player {
+        ("starplayer", "human")
+    } else {
+        ("human", "starplayer")
+    }
+}
+
+fn save_record(ai: &StarAI, path: &Option<String>, is_first_player: bool) {
+    if let Some(ref path) = *path {
+        let (first, second) = player_names(is_first_player);
+        GameRecord::from_ai(ai, first, second).save(path).unwrap_or_else(|e| {
+            println!("Failed to save record: {}", e);
+        });
     }
 }
 
 fn main() {
-    let mut ai = StarAI::new(SIZE);
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("r", "record", "write a record of the game to FILE after each move", "FILE");
+    opts.optopt("l", "load", "continue the game recorded in FILE", "FILE");
+    opts.optopt("", "main-time", &format!("main time for the computer in seconds (default {})", MAIN_TIME), "SECONDS");
+    opts.optopt("", "increment", &format!("time added after each computer move in seconds (default {})", INCREMENT), "SECONDS");
+    opts.optflag("", "ponder", "keep searching while waiting for your move");
+    GameConfig::add_options(&mut opts);
+    SearchConfig::add_options(&mut opts);
+    add_thread_options(&mut opts);
+    PatternLibrary::add_options(&mut opts);
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => { m },
+        Err(f) => { panic!("{}", f) },
+    };
+    let record_path = matches.opt_str("r");
+    let ponder = matches.opt_present("ponder");
+    let config = GameConfig::from_matches(&matches, GameConfig::new(SIZE, KOMI)).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let search_config = SearchConfig::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let patterns = PatternLibrary::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let threads = threads_from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let parse_seconds = |name: &str, default: u64| match matches.opt_str(name) {
+        None => Duration::from_secs(default),
+        Some(value) => Duration::from_secs(value.parse().unwrap_or_else(|_| {
+            panic!("Invalid value '{}' for --{}", value, name);
+        })),
+    };
+
+    let mut ai = match matches.opt_str("l") {
+        None => StarAI::with_search_config(config, search_config),
+        Some(path) => {
+            let mut ai = GameRecord::load(&path).and_then(|record| record.replay()).unwrap_or_else(|e| {
+                panic!("Failed to load record {}: {}", path, e);
+            });
+            ai.set_search_config(search_config).unwrap();
+            ai
+        },
+    };
+    ai.set_patterns(patterns);
+    ai.set_threads(threads);
+    let mut clock = Clock::new(TimeControl::Fischer {
+        main: parse_seconds("main-time", MAIN_TIME),
+        increment: parse_seconds("increment", INCREMENT),
+    });
 
     let is_first_player = read_line().unwrap().parse::<u8>().unwrap() == 0;
+    let ai_player = if is_first_player { Player::First } else { Player::Second };
 
-    if is_first_player {
-        make_move(&mut ai);
+    if ai.player_turn() == ai_player && !ai.finished() {
+        make_move(&mut ai, &mut clock, ponder);
+        save_record(&ai, &record_path, is_first_player);
     }
     ai.print_board();
 
-    loop {
+    while !ai.finished() {
         let line = read_line().unwrap();
+        if line == "undo" {
+            // Take back the computer's reply along with our own last move
+            if ai.history().len() < 2 {
+                println!("Nothing to undo");
+            } else {
+                ai.undo();
+                ai.undo();
+                ai.print_board();
+                save_record(&ai, &record_path, is_first_player);
+            }
+            continue;
+        }
+        if line == "ownership" {
+            ai.print_ownership();
+            continue;
+        }
+        if line == "score" {
+            // Explain each player's score group by group
+            for player in PLAYERS.iter() {
+                ai.print_groups(*player);
+            }
+            continue;
+        }
         let coords = line.split(" ").collect::<Vec<&str>>();
         if coords.len() != 2 {
             println!("You must input two numbers");
             continue;
         }
-        let x: usize = coords[0].parse().unwrap();
-        let y: usize = coords[1].parse().unwrap();
-        ai.add_move(x - 1, y - 1);
+        let (x, y) = match (coords[0].parse::<usize>(), coords[1].parse::<usize>()) {
+            (Ok(x), Ok(y)) => (x, y),
+            _ => {
+                println!("You must input two numbers");
+                continue;
+            },
+        };
+        if x == 0 || y == 0 {
+            println!("{}", MoveError::OffBoard);
+            continue;
+        }
+        if let Err(e) = ai.add_move(x - 1, y - 1) {
+            println!("{}", e);
+            continue;
+        }
         ai.print_board();
         print_scores(&ai);
+        save_record(&ai, &record_path, is_first_player);
+        if ai.finished() {
+            break;
+        }
 
-        make_move(&mut ai);
+        make_move(&mut ai, &mut clock, ponder);
         ai.print_board();
         print_scores(&ai);
+        save_record(&ai, &record_path, is_first_player);
     }
+    // The game ends as soon as it is decided, which may be before the board is full
+    println!("{:?} wins", ai.winner().unwrap());
 }
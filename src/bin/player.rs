@@ -1,84 +1,374 @@
+/*!
+ * Engine for use by GUIs, tournament managers and the compare binary, speaking a text protocol
+ * modelled on GTP.
+ *
+ * Each command is a line consisting of an optional numeric id, the command name and its
+ * arguments. Text following a `#` is ignored. A successful response is `=` followed by the id (if
+ * given), a space and the result, and a failure is the same but starting with `?` and giving an
+ * error message. Each response is terminated by a blank line.
+ *
+ * Players are written `first` and `second` (`b`/`black` and `w`/`white` are also accepted), and
+ * points are written `x,y` using the zero based coordinates used by `StarAI`.
+ *
+ * Supported commands are:
+ *
+ * - `protocol_version`, `name`, `version`, `known_command COMMAND`, `list_commands`
+ * - `boardsize SIZE`: start a new game on a board with sides of length `SIZE`
+ * - `komi KOMI`: set the komi, which may not change once moves have been played
+ * - `clear_board`: start a new game with the same rules
+ * - `play PLAYER POINT`: play a move for the given player, who must be the player to move
+ * - `genmove PLAYER`: think of, play and return a move for the given player
+ * - `undo`: take back the last move
+ * - `time_settings MAIN_TIME BYO_YOMI_TIME BYO_YOMI_STONES`: set the time control, in seconds,
+ *   using Canadian byo-yomi
+ * - `kgs-time_settings none|absolute|byoyomi|canadian|fischer ...`: set the time control, where
+ *   `absolute` takes the main time, `byoyomi` the main time, period time and number of periods,
+ *   `canadian` the main time, period time and number of stones, and `fischer` the main time and
+ *   increment
+ * - `time_left PLAYER TIME STONES`: set the time remaining to the engine, where `STONES` is the
+ *   number of stones or periods remaining in overtime, or 0 if in main time
+ * - `final_score`: the winner and their score, e.g. `first+3`, once the game is decided
+ * - `showboard`: the current board
+ * - `quit`
+ */
+
+extern crate getopts;
 extern crate starplayer;
 extern crate time;
 
-use std::io::{self, Write};
+use std::env;
+use std::io::{self, Write, BufRead};
 use std::str::FromStr;
-use std::time::SystemTime;
+use std::time::{Duration, Instant};
+
+use getopts::Options;
 
 use starplayer::StarAI;
+use starplayer::config::{GameConfig, SearchConfig, add_thread_options, threads_from_matches};
+use starplayer::patterns::PatternLibrary;
+use starplayer::player::Player;
+use starplayer::protocol::{parse_player, format_player, parse_point, format_point};
+use starplayer::record::GameRecord;
+use starplayer::time_control::{self, Budget, Clock, TimeControl};
 
-// TODO: these type of constants shouldn't be duplicated here
 const SIZE: usize = 7;
+/// Time to spend on each move if no time control has been set
 const MOVE_TIME_MS: u64 = 300;
 const KOMI: isize = 1;
 const ITERATIONS: usize = 10;
 
-fn try_input<F: FromStr>() -> Option<F> {
-    let mut buffer = String::new();
-    match io::stdin().read_line(&mut buffer) {
-        Ok(_) => {
-            // TODO: show error
-            Some(buffer.trim().parse::<F>().unwrap_or_else(|_| {
-                panic!("Failed to parse line: '{}'", buffer);
-            }))
-        },
-        Err(_) => None,
+const COMMANDS: [&'static str; 17] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "boardsize",
+    "komi",
+    "clear_board",
+    "play",
+    "genmove",
+    "undo",
+    "time_settings",
+    "kgs-time_settings",
+    "time_left",
+    "final_score",
+    "showboard",
+    "quit",
+];
+
+fn parse_arg<F: FromStr>(args: &[&str], index: usize) -> Result<F, String> {
+    match args.get(index) {
+        None => Err("missing argument".to_string()),
+        Some(arg) => arg.parse::<F>().map_err(|_| format!("invalid argument '{}'", arg)),
     }
 }
 
-fn write_or_panic<F: ToString>(value: F) {
-    io::stdout().write(value.to_string().as_bytes()).unwrap_or_else(|e| {
-        panic!("Failed to write value: {}", e);
-    });
-    io::stdout().write(b"\n").unwrap();
-    io::stdout().flush().unwrap();
+fn parse_seconds(args: &[&str], index: usize) -> Result<Duration, String> {
+    parse_arg(args, index).map(Duration::from_secs)
 }
 
-fn make_move(ai: &mut StarAI) {
-    let start_time = SystemTime::now();
-    let mut iterations = 0;
-    loop {
-        iterations += ITERATIONS;
-        ai.calculate(ITERATIONS, KOMI);
-        let elapsed = start_time.elapsed().unwrap();
-        let elapsed_milliseconds = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() as u64 / 1000000);
-        if elapsed_milliseconds > MOVE_TIME_MS {
-            break;
-        }
+/// Parse the arguments of `kgs-time_settings`, giving `None` for no time limit
+fn parse_kgs_time_settings(args: &[&str]) -> Result<Option<TimeControl>, String> {
+    match args.get(0).map(|style| style.to_lowercase()).unwrap_or_default().as_str() {
+        "none" => Ok(None),
+        "absolute" => Ok(Some(TimeControl::SuddenDeath {
+            main: parse_seconds(args, 1)?,
+        })),
+        "byoyomi" => Ok(Some(TimeControl::ByoYomi {
+            main: parse_seconds(args, 1)?,
+            period: parse_seconds(args, 2)?,
+            periods: parse_arg(args, 3)?,
+        })),
+        "canadian" => Ok(Some(TimeControl::Canadian {
+            main: parse_seconds(args, 1)?,
+            period: parse_seconds(args, 2)?,
+            stones: parse_arg(args, 3)?,
+        })),
+        "fischer" => Ok(Some(TimeControl::Fischer {
+            main: parse_seconds(args, 1)?,
+            increment: parse_seconds(args, 2)?,
+        })),
+        style => Err(format!("unknown time system '{}'", style)),
     }
-    writeln!(io::stderr(), "Iterations: {}", iterations).unwrap();
-    let (x, y) = ai.best_move();
-    ai.add_move(x, y);
-    write_or_panic(x);
-    write_or_panic(y);
 }
 
-fn main() {
-    let mut ai = StarAI::new(SIZE);
+struct Engine {
+    ai: StarAI,
+    record_path: Option<String>,
+    /// The player whose moves have been generated, used to name the players in records
+    engine_player: Option<Player>,
+    /// The engine's clock, or `None` if there is no time limit
+    clock: Option<Clock>,
+    /// Time to spend on each move when there is no time limit
+    move_time: Duration,
+    /// Whether to keep searching while waiting for the opponent's move
+    ponder: bool,
+}
+
+impl Engine {
+    fn save_record(&self) {
+        if let Some(ref path) = self.record_path {
+            let (first, second) = match self.engine_player {
+                Some(Player::First) => ("starplayer", "opponent"),
+                Some(Player::Second) => ("opponent", "starplayer"),
+                None => ("unknown", "unknown"),
+            };
+            GameRecord::from_ai(&self.ai, first, second).save(path).unwrap_or_else(|e| {
+                writeln!(io::stderr(), "Failed to save record: {}", e).unwrap();
+            });
+        }
+    }
 
-    let is_first_player = try_input::<u8>().unwrap() == 0;
+    /// Time to spend thinking about the next move
+    fn budget(&self) -> Budget {
+        match self.clock {
+            None => Budget::fixed(self.move_time),
+            Some(ref clock) => clock.budget(self.ai.empty_cells()),
+        }
+    }
+
+    fn check_turn(&self, player: Player) -> Result<(), String> {
+        if self.ai.player_turn() != player {
+            Err(format!("it is not {}'s turn", format_player(player)))
+        } else {
+            Ok(())
+        }
+    }
 
-    if is_first_player {
-        make_move(&mut ai);
+    fn genmove(&mut self, player: Player) -> Result<String, String> {
+        self.check_turn(player)?;
+        if self.ai.finished() {
+            return Err("game is over".to_string());
+        }
+        let start_time = Instant::now();
+        let ponder_iterations = self.ai.stop_pondering();
+        if self.ponder {
+            writeln!(io::stderr(), "Ponder iterations: {}", ponder_iterations).unwrap();
+        }
+        let budget = self.budget();
+        let iterations = time_control::think(&mut self.ai, &budget, ITERATIONS);
+        writeln!(io::stderr(), "Iterations: {}", iterations).unwrap();
+        let (x, y) = self.ai.best_move();
+        self.ai.add_move(x, y).unwrap();
+        if let Some(ref mut clock) = self.clock {
+            clock.record_move(start_time.elapsed());
+        }
+        self.engine_player = Some(player);
+        self.save_record();
+        if self.ponder {
+            self.ai.start_pondering();
+        }
+        Ok(format_point((x, y)))
     }
 
-    loop {
-        let x = try_input();
-        let y = try_input();
-        match (x, y) {
-            (Some(x), Some(y)) => {
-                ai.add_move(x, y);
-                if ai.finished(KOMI) {
-                    break;
+    fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("starplayer".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => {
+                let name = parse_arg::<String>(args, 0)?;
+                Ok(COMMANDS.contains(&name.as_str()).to_string())
+            },
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "boardsize" => {
+                let size = parse_arg(args, 0)?;
+                let komi = self.ai.config().komi;
+                self.ai.clear();
+                self.ai.set_config(GameConfig::new(size, komi)).map_err(|_| "unacceptable size".to_string())?;
+                self.save_record();
+                Ok(String::new())
+            },
+            "komi" => {
+                let komi = parse_arg(args, 0)?;
+                let size = self.ai.size();
+                self.ai.set_config(GameConfig::new(size, komi)).map_err(|e| e.to_string())?;
+                self.save_record();
+                Ok(String::new())
+            },
+            "clear_board" => {
+                self.ai.clear();
+                self.save_record();
+                Ok(String::new())
+            },
+            "play" => {
+                let player = parse_player(args.get(0).unwrap_or(&""))?;
+                let (x, y) = parse_point(args.get(1).unwrap_or(&""))?;
+                self.check_turn(player)?;
+                self.ai.add_move(x, y).map_err(|e| format!("illegal move: {}", e))?;
+                self.save_record();
+                Ok(String::new())
+            },
+            "genmove" => {
+                let player = parse_player(args.get(0).unwrap_or(&""))?;
+                self.genmove(player)
+            },
+            "undo" => {
+                match self.ai.undo() {
+                    None => Err("cannot undo".to_string()),
+                    Some(_) => {
+                        self.save_record();
+                        Ok(String::new())
+                    },
+                }
+            },
+            "time_settings" => {
+                let main = parse_seconds(args, 0)?;
+                let period = parse_seconds(args, 1)?;
+                let stones = parse_arg(args, 2)?;
+                self.clock = if period.as_secs() == 0 {
+                    if main.as_secs() == 0 {
+                        None
+                    } else {
+                        Some(Clock::new(TimeControl::SuddenDeath { main: main }))
+                    }
+                } else {
+                    Some(Clock::new(TimeControl::Canadian { main: main, period: period, stones: stones }))
+                };
+                Ok(String::new())
+            },
+            "kgs-time_settings" => {
+                self.clock = parse_kgs_time_settings(args)?.map(Clock::new);
+                Ok(String::new())
+            },
+            "time_left" => {
+                let player = parse_player(args.get(0).unwrap_or(&""))?;
+                let time = parse_seconds(args, 1)?;
+                let count = parse_arg(args, 2)?;
+                // Only the engine's own clock is tracked
+                if self.engine_player.map_or(true, |engine_player| engine_player == player) {
+                    if let Some(ref mut clock) = self.clock {
+                        clock.set_remaining(time, count);
+                    }
                 }
-                make_move(&mut ai);
-                if ai.finished(KOMI) {
-                    break;
+                Ok(String::new())
+            },
+            "final_score" => {
+                match self.ai.winner() {
+                    None => Err("game is not finished".to_string()),
+                    Some(winner) => Ok(format!("{}+{}", format_player(winner), self.ai.score(winner))),
                 }
             },
-            _ => {
-                break;
-            }
+            "showboard" => Ok(format!("\n{}", self.ai.format_board())),
+            "quit" => Ok(String::new()),
+            _ => Err("unknown command".to_string()),
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut opts = Options::new();
+    opts.optopt("r", "record", "write a record of the game to FILE after each move", "FILE");
+    opts.optopt("l", "load", "continue the game recorded in FILE", "FILE");
+    opts.optopt("", "move-time", &format!("milliseconds to spend on each move without a time limit (default {})", MOVE_TIME_MS), "MS");
+    opts.optflag("", "ponder", "keep searching while waiting for the opponent's move");
+    GameConfig::add_options(&mut opts);
+    SearchConfig::add_options(&mut opts);
+    add_thread_options(&mut opts);
+    PatternLibrary::add_options(&mut opts);
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => { m },
+        Err(f) => { panic!("{}", f) },
+    };
+    let config = GameConfig::from_matches(&matches, GameConfig::new(SIZE, KOMI)).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let search_config = SearchConfig::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let patterns = PatternLibrary::from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let threads = threads_from_matches(&matches).unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+    let move_time_ms = match matches.opt_str("move-time") {
+        None => MOVE_TIME_MS,
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            panic!("Invalid value '{}' for --move-time", value);
+        }),
+    };
+
+    let mut ai = match matches.opt_str("l") {
+        None => StarAI::with_search_config(config, search_config),
+        Some(path) => {
+            let mut ai = GameRecord::load(&path).and_then(|record| record.replay()).unwrap_or_else(|e| {
+                panic!("Failed to load record {}: {}", path, e);
+            });
+            ai.set_search_config(search_config).unwrap();
+            ai
+        },
+    };
+    ai.set_patterns(patterns);
+    ai.set_threads(threads);
+    let mut engine = Engine {
+        ai: ai,
+        record_path: matches.opt_str("r"),
+        engine_player: None,
+        clock: None,
+        move_time: Duration::from_millis(move_time_ms),
+        ponder: matches.opt_present("ponder"),
+    };
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap_or_else(|e| {
+            panic!("Failed to read command: {}", e);
+        });
+        let line = match line.find('#') {
+            None => &line[..],
+            Some(comment) => &line[..comment],
+        };
+        let mut words = line.split_whitespace().collect::<Vec<&str>>();
+        if words.is_empty() {
+            continue;
+        }
+        let id = match words[0].parse::<u32>() {
+            Ok(id) => {
+                words.remove(0);
+                id.to_string()
+            },
+            Err(_) => String::new(),
+        };
+        let (command, command_args) = match words.split_first() {
+            None => ("", &words[..]),
+            Some((command, command_args)) => (*command, command_args),
+        };
+        let response = match engine.execute(command, command_args) {
+            Ok(result) => format!("={} {}", id, result),
+            Err(message) => format!("?{} {}", id, message),
+        };
+        let mut stdout = io::stdout();
+        write!(stdout, "{}\n\n", response.trim_end()).unwrap_or_else(|e| {
+            panic!("Failed to write response: {}", e);
+        });
+        stdout.flush().unwrap();
+        if command == "quit" {
+            break;
         }
     }
+    engine.ai.stop_pondering();
 }
@@ -1,7 +1,8 @@
 #![feature(link_args)]
 extern crate starplayer;
 
-use starplayer::StarAI;
+use starplayer::{StarAI, MoveError};
+use starplayer::config::GameConfig;
 use std::mem::transmute;
 
 const KOMI: isize = 1;
@@ -11,13 +12,13 @@ extern {}
 
 #[no_mangle]
 pub extern fn starplayer_new(size: i32) ->  *mut StarAI {
-    unsafe { transmute(Box::new(StarAI::new(size as usize))) }
+    unsafe { transmute(Box::new(StarAI::new(GameConfig::new(size as usize, KOMI)))) }
 }
 
 #[no_mangle]
 pub extern fn starplayer_calculate(ptr: *mut StarAI, iterations: i32) {
     let mut star = unsafe { &mut *ptr };
-    star.calculate(iterations as usize, KOMI);
+    star.calculate(iterations as usize);
 }
 
 // Move is returned as x + y * (size + size - 1), since complex return types are hard
@@ -29,10 +30,19 @@ pub extern fn starplayer_best_move(ptr: *mut StarAI) -> i32 {
     (x + y * (size + size - 1)) as i32
 }
 
+// Returns 0 if the move was played, otherwise a code identifying why it is illegal
 #[no_mangle]
-pub extern fn starplayer_add_move(ptr: *mut StarAI, x: i32, y: i32) {
+pub extern fn starplayer_add_move(ptr: *mut StarAI, x: i32, y: i32) -> i32 {
     let mut star = unsafe { &mut *ptr };
-    star.add_move(x as usize, y as usize);
+    if x < 0 || y < 0 {
+        return 1;
+    }
+    match star.add_move(x as usize, y as usize) {
+        Ok(()) => 0,
+        Err(MoveError::OffBoard) => 1,
+        Err(MoveError::Occupied) => 2,
+        Err(MoveError::GameOver) => 3,
+    }
 }
 
 #[no_mangle]
@@ -3,25 +3,27 @@ use std::hash::{Hash, Hasher};
 use rand::{Rng, XorShiftRng};
 
 use statistics::Stats;
-use info::{BoardInfo, Pattern};
-use player::Player;
+use groups::{Groups, group_value};
+use info::BoardInfo;
+use policy::PlayoutPolicy;
+use player::{Player, PLAYERS};
 use boardvec::BoardVec;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub struct BoardState {
+pub struct BoardState<const W: usize> {
     pub moves: usize,
-    pub first_player: BoardVec,
-    pub second_player: BoardVec,
+    pub first_player: BoardVec<W>,
+    pub second_player: BoardVec<W>,
 }
 
-impl Hash for BoardState {
+impl<const W: usize> Hash for BoardState<W> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.first_player.hash(state);
         self.second_player.hash(state);
     }
 }
 
-fn dfs_points(info: &BoardInfo, player: BoardVec, this_player: bool, visited: &mut BoardVec, point: usize) -> usize {
+fn dfs_points<const W: usize>(info: &BoardInfo<W>, player: BoardVec<W>, this_player: bool, visited: &mut BoardVec<W>, point: usize) -> usize {
     let mut result = 0;
     if info.on_boundary(point) {
         result += 1;
@@ -36,9 +38,48 @@ fn dfs_points(info: &BoardInfo, player: BoardVec, this_player: bool, visited: &m
     result
 }
 
-impl BoardState {
-    pub fn new(info: &BoardInfo) -> BoardState {
-        if info.count > BoardVec::size() {
+/// A connected group of points counted when scoring
+pub struct ScoreGroup {
+    /// Whether the group belongs to the player being scored, rather than their opponent
+    pub own: bool,
+    pub points: Vec<usize>,
+    /// The points of the group on the boundary of the board
+    pub boundary: Vec<usize>,
+}
+
+/**
+ * Returns a representation of the board with coordinate labels, using `cell` to get the character
+ * shown for each point
+ */
+pub fn format_cells<const W: usize, F: Fn(usize) -> char>(info: &BoardInfo<W>, cell: F) -> String {
+    let mut result = String::new();
+    result.push(' ');
+    for x in 0..info.coords_range {
+        result.push_str(&format!(" {}", (x + 1) % 10));
+    }
+    result.push('\n');
+    for y in 0..info.coords_range {
+        let padding = std::iter::repeat(" ").take(y + 1).collect::<String>();
+        result.push_str(&format!("{}{}", padding, (y + 1) % 10));
+        for x in 0..info.coords_range {
+            result.push(' ');
+            match info.reverse_coords.get(&(x, y)) {
+                None => {
+                    result.push('_');
+                },
+                Some(&idx) => {
+                    result.push(cell(idx));
+                },
+            }
+        }
+        result.push('\n');
+    }
+    result
+}
+
+impl<const W: usize> BoardState<W> {
+    pub fn new(info: &BoardInfo<W>) -> BoardState<W> {
+        if info.count > BoardVec::<W>::size() {
             panic!("BoardVec does not support boards of this size");
         }
         BoardState {
@@ -71,10 +112,22 @@ impl BoardState {
         self.moves += 1;
     }
 
+    /**
+     * Removes the last move, which must have been played at the given index
+     */
+    pub fn remove_move(&mut self, i: usize) {
+        self.moves -= 1;
+        if self.moves%2 == 0 {
+            self.first_player.clear(i);
+        } else {
+            self.second_player.clear(i);
+        }
+    }
+
     /**
      * Returns whether the board has been filled
      */
-    pub fn finished(&self, info: &BoardInfo) -> bool {
+    pub fn finished(&self, info: &BoardInfo<W>) -> bool {
         self.moves == info.count
     }
 
@@ -83,7 +136,7 @@ impl BoardState {
      * occupied by their opponent (thus giving their minimum possible score). If this is greater
      * than zero, then they have won.
      */
-    pub fn player_score(&self, info: &BoardInfo, player: Player, komi: isize) -> isize {
+    pub fn player_score(&self, info: &BoardInfo<W>, player: Player, komi: isize) -> isize {
         let player_points = match player {
             Player::First => self.first_player,
             Player::Second => self.second_player,
@@ -100,24 +153,103 @@ impl BoardState {
             }
             let this_player = player_points.get(i);
             visited.set(i);
-            let score = dfs_points(info, player_points, this_player, &mut visited, i) as isize;
-            let multiplier = if this_player { 1 } else { -1 };
-            if score < 2 {
-                player_score += (-1) * multiplier * score;
+            let value = group_value(dfs_points(info, player_points, this_player, &mut visited, i));
+            if this_player {
+                player_score += value;
             } else {
-                player_score += multiplier * (score - 4);
+                player_score -= value;
             }
         }
         player_score
     }
 
+    /**
+     * Returns the groups counted by `player_score`, i.e. the connected groups of the player's
+     * points and the connected groups of all other points, ordered by their lowest point
+     */
+    pub fn score_groups(&self, info: &BoardInfo<W>, player: Player) -> Vec<ScoreGroup> {
+        let player_points = match player {
+            Player::First => self.first_player,
+            Player::Second => self.second_player,
+        };
+        let mut visited = BoardVec::<W>::new();
+        let mut groups = Vec::new();
+        for i in 0..info.count {
+            if visited.get(i) {
+                continue;
+            }
+            let own = player_points.get(i);
+            let mut group = ScoreGroup {
+                own: own,
+                points: Vec::new(),
+                boundary: Vec::new(),
+            };
+            visited.set(i);
+            let mut stack = vec![i];
+            while let Some(point) = stack.pop() {
+                group.points.push(point);
+                if info.on_boundary(point) {
+                    group.boundary.push(point);
+                }
+                for &other_point in info.adjacencies[point].iter() {
+                    if !visited.get(other_point) && own == player_points.get(other_point) {
+                        visited.set(other_point);
+                        stack.push(other_point);
+                    }
+                }
+            }
+            group.points.sort();
+            group.boundary.sort();
+            groups.push(group);
+        }
+        groups
+    }
+
     /**
      * Returns whether the given player has won the game
      */
-    pub fn is_winner(&self, info: &BoardInfo, player: Player, komi: isize) -> bool {
+    pub fn is_winner(&self, info: &BoardInfo<W>, player: Player, komi: isize) -> bool {
         self.player_score(info, player, komi) > 0
     }
 
+    /**
+     * Returns the winner if the result can no longer change, i.e. if one player has won however
+     * the remaining points are filled. A full board where neither player has a positive score is
+     * counted as a win for the second player.
+     */
+    pub fn decided(&self, info: &BoardInfo<W>, komi: isize) -> Option<Player> {
+        let winner = PLAYERS.iter().cloned().find(|&player| self.is_winner(info, player, komi));
+        if winner.is_none() && self.finished(info) {
+            Some(Player::Second)
+        } else {
+            winner
+        }
+    }
+
+    /**
+     * Returns whether this state can be reached by playing further moves from `earlier`
+     */
+    pub fn follows(&self, earlier: &BoardState<W>) -> bool {
+        self.moves >= earlier.moves &&
+            self.first_player.contains(&earlier.first_player) &&
+            self.second_player.contains(&earlier.second_player)
+    }
+
+    /**
+     * Returns the groups formed by the stones on the board
+     */
+    pub fn groups(&self, info: &BoardInfo<W>) -> Groups {
+        let mut groups = Groups::new(info);
+        for i in 0..info.count {
+            if self.first_player.get(i) {
+                groups.add(info, i, Player::First);
+            } else if self.second_player.get(i) {
+                groups.add(info, i, Player::Second);
+            }
+        }
+        groups
+    }
+
     /**
      * Returns true if either player has played at the given position
      */
@@ -126,21 +258,24 @@ impl BoardState {
     }
 
     /**
-     * Play random games starting from this game state, and return win statistics
+     * Play random games starting from this game state with moves chosen by `policy`, and return
+     * win statistics. Games are stopped once decided, so only the points played before then are
+     * recorded.
      */
-    pub fn play_random(self, info: &BoardInfo, rng: &mut XorShiftRng, komi: isize, iterations: u32) -> Stats {
+    pub fn play_random<P: PlayoutPolicy<W>>(self, info: &BoardInfo<W>, policy: &P, rng: &mut XorShiftRng, komi: isize, iterations: u32) -> Stats {
         let mut result = Stats::new(info);
+        let groups = self.groups(info);
         for _ in 0..iterations {
             let mut new_state = self;
-            new_state.play_random_inner(info, rng);
-            let winner = if new_state.is_winner(info, Player::First, komi) {
-                Player::First
-            } else {
-                Player::Second
-            };
+            let mut new_groups = groups.clone();
+            let winner = new_state.play_random_inner(info, policy, rng, komi, &mut new_groups);
             result.record_game(winner);
             for i in 0..info.count {
-                result.record_point(winner, i, if new_state.first_player.get(i) { Player::First } else { Player::Second});
+                if new_state.first_player.get(i) {
+                    result.record_point(winner, i, Player::First);
+                } else if new_state.second_player.get(i) {
+                    result.record_point(winner, i, Player::Second);
+                }
             }
         }
         result
@@ -149,40 +284,36 @@ impl BoardState {
     /**
      * Print a representation of the current board to standard output
      */
-    pub fn print_board(&self, info: &BoardInfo) {
-        print!(" ");
-        for x in 0..info.coords_range {
-            print!(" {}", (x + 1) % 10);
-        }
-        println!("");
-        for y in 0..info.coords_range {
-            let padding = std::iter::repeat(" ").take(y + 1).collect::<String>();
-            print!("{}{}", padding, (y + 1) % 10);
-            for x in 0..info.coords_range {
-                print!(" ");
-                match info.reverse_coords.get(&(x, y)) {
-                    None => {
-                        print!("_");
-                    },
-                    Some(&idx) => {
-                        if self.first_player.get(idx) {
-                            if self.second_player.get(idx) {
-                                panic!("Both players present at same board position");
-                            }
-                            print!("X");
-                        } else if self.second_player.get(idx) {
-                            print!("O");
-                        } else {
-                            print!(".");
-                        }
-                    },
+    pub fn print_board(&self, info: &BoardInfo<W>) {
+        print!("{}", self.format_board(info));
+    }
+
+    /**
+     * Returns a representation of the current board, with `X` for the first player and `O` for
+     * the second
+     */
+    pub fn format_board(&self, info: &BoardInfo<W>) -> String {
+        format_cells(info, |idx| {
+            if self.first_player.get(idx) {
+                if self.second_player.get(idx) {
+                    panic!("Both players present at same board position");
                 }
+                'X'
+            } else if self.second_player.get(idx) {
+                'O'
+            } else {
+                '.'
             }
-            println!("");
-        }
+        })
     }
 
-    fn play_random_inner(&mut self, info: &BoardInfo, rng: &mut XorShiftRng) {
+    /**
+     * Play moves chosen by `policy`, or random moves where it has no preference, until the game
+     * is decided, returning the winner. Checking exactly whether
+     * the game is decided is expensive compared to playing a move, so the lower bound on scores
+     * given by `groups` is used instead.
+     */
+    fn play_random_inner<P: PlayoutPolicy<W>>(&mut self, info: &BoardInfo<W>, policy: &P, rng: &mut XorShiftRng, komi: isize, groups: &mut Groups) -> Player {
         let mut unplayed = Vec::with_capacity(info.count - self.moves);
         for i in 0..info.count {
             if !self.any(i) {
@@ -192,30 +323,23 @@ impl BoardState {
         rng.shuffle(&mut unplayed);
         let mut index = 0;
         let mut last_played = None;
+        for player in PLAYERS.iter() {
+            if groups.min_score(*player, komi) > 0 {
+                return *player;
+            }
+        }
         loop {
             if self.finished(info) {
-                break;
+                return if groups.score(Player::First, komi) > 0 {
+                    Player::First
+                } else {
+                    Player::Second
+                };
             }
 
             let is_first_player = self.moves%2 == 0;
 
-            let mut to_play = None;
-
-            // Try playing pattern first
-            if let Some(last_pos) = last_played {
-                let patterns_for_pos: &Vec<Pattern> = &info.patterns[last_pos];
-                for pattern in patterns_for_pos.iter() {
-                    let (last_player, current_player) = if is_first_player {
-                        (self.second_player, self.first_player)
-                    } else {
-                        (self.first_player, self.second_player)
-                    };
-                    if let Some(pos) = pattern.check(&last_player, &current_player) {
-                        to_play = Some(pos);
-                        break;
-                    }
-                }
-            }
+            let mut to_play = policy.choose(info, self, last_played, rng);
 
             // Play next empty position otherwise
             if let None = to_play {
@@ -228,6 +352,8 @@ impl BoardState {
             }
 
             if let Some(pos) = to_play {
+                let player = self.player_turn();
+                groups.add(info, pos, player);
                 if is_first_player {
                     self.first_player.set(pos);
                 } else {
@@ -235,6 +361,10 @@ impl BoardState {
                 }
                 self.moves += 1;
                 last_played = Some(pos);
+                // Only the score of the player who moved can have changed
+                if groups.min_score(player, komi) > 0 {
+                    return player;
+                }
             }
         }
     }
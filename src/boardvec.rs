@@ -1,44 +1,56 @@
+use std::hash::{Hash, Hasher};
+
 const U64_LOG: usize = 6;
+const U64_MASK: usize = (1 << U64_LOG) - 1;
+
+/// Fixed length bit vector used to store board positions, made up of `W` 64 bit words
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoardVec<const W: usize> {
+    words: [u64; W],
+}
 
-/// Fixed length bit vector used to store board positions
-#[derive(Copy, Clone, Hash, Debug, PartialEq, Eq)]
-pub struct BoardVec {
-    word0: u64,
-    word1: u64
+/// Number of words needed for a `BoardVec` holding `count` points
+pub fn words_for(count: usize) -> usize {
+    (count + U64_MASK) >> U64_LOG
 }
 
-impl BoardVec {
+impl<const W: usize> BoardVec<W> {
     pub fn new() -> Self {
-        BoardVec { word0: 0, word1: 0 }
+        BoardVec { words: [0; W] }
     }
 
     pub fn size() -> usize {
-        2 << U64_LOG
+        W << U64_LOG
     }
 
     pub fn set(&mut self, idx: usize) {
-        if idx < 64 {
-            self.word0 |= 1 << idx;
-        } else {
-            self.word1 |= 1 << (idx - 64);
-        }
+        self.words[idx >> U64_LOG] |= 1 << (idx & U64_MASK);
+    }
+
+    pub fn clear(&mut self, idx: usize) {
+        self.words[idx >> U64_LOG] &= !(1 << (idx & U64_MASK));
     }
 
     pub fn get(&self, idx: usize) -> bool {
-        if idx < 64 {
-            (self.word0 & (1 << idx)) != 0
-        } else {
-            (self.word1 & (1 << (idx - 64))) != 0
-        }
+        (self.words[idx >> U64_LOG] & (1 << (idx & U64_MASK))) != 0
     }
 
     /// Whether this vector has any 1 bits in common with `other`
-    pub fn intersects(&self, other: &BoardVec) -> bool {
-        ((self.word0 & other.word0) != 0) || ((self.word1 & other.word1) != 0)
+    pub fn intersects(&self, other: &BoardVec<W>) -> bool {
+        self.words.iter().zip(other.words.iter()).any(|(a, b)| (a & b) != 0)
     }
 
     /// Whether all bits set to 1 in `other` are also 1 in this vector
-    pub fn contains(&self, other: &BoardVec) -> bool {
-        ((self.word0 & other.word0) == other.word0) && ((self.word1 & other.word1) == other.word1)
+    pub fn contains(&self, other: &BoardVec<W>) -> bool {
+        self.words.iter().zip(other.words.iter()).all(|(a, b)| (a & b) == *b)
+    }
+}
+
+// Hash the words directly, rather than as a slice, to avoid hashing the length as well
+impl<const W: usize> Hash for BoardVec<W> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for word in self.words.iter() {
+            state.write_u64(*word);
+        }
     }
 }
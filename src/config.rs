@@ -0,0 +1,276 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+use getopts::{Matches, Options};
+
+use MAX_SIZE;
+
+/// Parse the value of an option added with `optopt`, giving `default` if it isn't present
+pub fn parse_opt<F: FromStr>(matches: &Matches, name: &str, default: F) -> Result<F, String> {
+    match matches.opt_str(name) {
+        None => Ok(default),
+        Some(value) => value.parse().map_err(|_| format!("Invalid value '{}' for --{}", value, name)),
+    }
+}
+
+/// The rules of a game, fixed for its whole duration
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GameConfig {
+    /// The length of a side of the board
+    pub size: usize,
+    /// Points given to the second player to compensate for moving second
+    pub komi: isize,
+}
+
+impl GameConfig {
+    pub fn new(size: usize, komi: isize) -> GameConfig {
+        GameConfig {
+            size: size,
+            komi: komi,
+        }
+    }
+
+    /// Check that the configuration is supported, i.e. that the size is between 2 and `MAX_SIZE`
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.size < 2 || self.size > MAX_SIZE {
+            Err(ConfigError::UnsupportedSize(self.size))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Add the `--size` and `--komi` command line options
+    pub fn add_options(opts: &mut Options) {
+        opts.optopt("", "size", "length of a side of the board", "SIZE");
+        opts.optopt("", "komi", "points given to the second player", "KOMI");
+    }
+
+    /// Read the options added by `add_options`, taking values which aren't given from `default`
+    pub fn from_matches(matches: &Matches, default: GameConfig) -> Result<GameConfig, String> {
+        let config = GameConfig {
+            size: parse_opt(matches, "size", default.size)?,
+            komi: parse_opt(matches, "komi", default.komi)?,
+        };
+        config.validate().map_err(|e| e.to_string())?;
+        Ok(config)
+    }
+}
+
+/// Exploration terms for selection, where `N` is the number of playouts through the parent and `n`
+/// the number through the child
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExplorationFormula {
+    /// UCB1, i.e. `sqrt(ln N / n)`
+    Ucb1,
+    /// UCB1-tuned, which scales the UCB1 term using an upper bound on the variance of the child's
+    /// results, capped at 1/4
+    Ucb1Tuned,
+}
+
+impl FromStr for ExplorationFormula {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<ExplorationFormula, ()> {
+        match s {
+            "ucb1" => Ok(ExplorationFormula::Ucb1),
+            "ucb1-tuned" => Ok(ExplorationFormula::Ucb1Tuned),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Policies for choosing the moves played in playouts
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PlayoutPolicyKind {
+    /// Uniformly random moves
+    Uniform,
+    /// Responses to the opponent's last move from local patterns, e.g. saving bridges, and
+    /// random moves otherwise
+    Patterns,
+    /// Responses from local patterns or random moves, chosen in proportion to the weights of the
+    /// patterns
+    Weighted,
+}
+
+impl FromStr for PlayoutPolicyKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<PlayoutPolicyKind, ()> {
+        match s {
+            "uniform" => Ok(PlayoutPolicyKind::Uniform),
+            "patterns" => Ok(PlayoutPolicyKind::Patterns),
+            "weighted" => Ok(PlayoutPolicyKind::Weighted),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Parameters of the search, which can be changed at any time without affecting the rules
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SearchConfig {
+    /// Number of playouts through a node at which its own winrate and its AMAF winrate would be
+    /// given equal weight, were it not for `amaf_limit`
+    pub amaf_equivalence: f64,
+    /// The largest weight given to the winrate of a node's own playouts over its AMAF winrate
+    pub amaf_limit: f64,
+    /// Number of playouts run from a leaf in each iteration
+    pub playouts_per_leaf: u32,
+    /// Weight of the exploration term in selection, where 0 selects purely by winrate
+    pub exploration: f64,
+    /// How the exploration term is calculated
+    pub exploration_formula: ExplorationFormula,
+    /// Value used in selection for children with no playouts or AMAF playouts, where values
+    /// above 1 ensure that every child is tried before any is revisited
+    pub first_play_urgency: f64,
+    /// How moves are chosen in playouts
+    pub playout_policy: PlayoutPolicyKind,
+    /// Number of playouts through a node before its children are created, which must be at least 1
+    pub expansion_threshold: u32,
+    /**
+     * Seed for the random number generator, or `None` to seed it randomly. With a seed, the same
+     * position and sequence of calls to `calculate` always give the same results for a given
+     * number of threads, and several threads then select paths in lockstep at some cost in speed.
+     * Results differ between thread counts, since each thread has its own random number generator
+     * and paths are selected in batches of one per thread. Pondering is never reproducible, since
+     * the number of iterations it runs depends on timing.
+     */
+    pub seed: Option<u64>,
+    /**
+     * Limit in megabytes on the memory used by the search tree, or `None` for no limit. The tree
+     * is allocated up front with as much room as fits within the limit. Once the tree is close to
+     * filling it, the children of the least visited nodes are evicted between calls to
+     * `calculate`, and until then nodes are not expanded if their children wouldn't fit.
+     */
+    pub max_memory: Option<usize>,
+    /**
+     * Number of winning AMAF playouts a new node starts with if its move answers an intrusion
+     * into one of the player's edge templates, so that the answer is searched first
+     */
+    pub template_prior: u32,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            amaf_equivalence: 1000.,
+            amaf_limit: 0.5,
+            playouts_per_leaf: 32,
+            exploration: 0.,
+            exploration_formula: ExplorationFormula::Ucb1,
+            first_play_urgency: 2.,
+            playout_policy: PlayoutPolicyKind::Patterns,
+            expansion_threshold: 1,
+            seed: None,
+            max_memory: None,
+            template_prior: 32,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Check that the parameters are within range
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(self.amaf_equivalence > 0.) {
+            Err(ConfigError::InvalidParameter("amaf-equivalence"))
+        } else if !(self.amaf_limit >= 0. && self.amaf_limit <= 1.) {
+            Err(ConfigError::InvalidParameter("amaf-limit"))
+        } else if self.playouts_per_leaf == 0 {
+            Err(ConfigError::InvalidParameter("playouts"))
+        } else if !(self.exploration >= 0.) {
+            Err(ConfigError::InvalidParameter("exploration"))
+        } else if self.first_play_urgency.is_nan() {
+            Err(ConfigError::InvalidParameter("fpu"))
+        } else if self.expansion_threshold == 0 {
+            Err(ConfigError::InvalidParameter("expansion-threshold"))
+        } else if self.max_memory == Some(0) {
+            Err(ConfigError::InvalidParameter("max-memory"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Add a command line option for each parameter
+    pub fn add_options(opts: &mut Options) {
+        opts.optopt("", "amaf-equivalence", "playouts at which a node's own winrate is weighted equally with its AMAF winrate", "N");
+        opts.optopt("", "amaf-limit", "largest weight given to a node's own winrate over its AMAF winrate", "WEIGHT");
+        opts.optopt("", "playouts", "playouts run from a leaf in each iteration", "N");
+        opts.optopt("", "exploration", "weight of the exploration term in selection", "C");
+        opts.optopt("", "exploration-formula", "exploration term to use, ucb1 or ucb1-tuned", "FORMULA");
+        opts.optopt("", "fpu", "value of children with no playouts in selection", "VALUE");
+        opts.optopt("", "playout-policy", "how moves are chosen in playouts, uniform, patterns or weighted", "POLICY");
+        opts.optopt("", "expansion-threshold", "playouts through a node before it is expanded", "N");
+        opts.optopt("", "seed", "seed for the random number generator", "SEED");
+        opts.optopt("", "max-memory", "approximate limit on the memory used by the search tree", "MB");
+        opts.optopt("", "template-prior", "winning playouts given to answers to intrusions into edge templates", "N");
+    }
+
+    /// Read the options added by `add_options`, using the defaults for any which aren't given
+    pub fn from_matches(matches: &Matches) -> Result<SearchConfig, String> {
+        let default = SearchConfig::default();
+        let config = SearchConfig {
+            amaf_equivalence: parse_opt(matches, "amaf-equivalence", default.amaf_equivalence)?,
+            amaf_limit: parse_opt(matches, "amaf-limit", default.amaf_limit)?,
+            playouts_per_leaf: parse_opt(matches, "playouts", default.playouts_per_leaf)?,
+            exploration: parse_opt(matches, "exploration", default.exploration)?,
+            exploration_formula: parse_opt(matches, "exploration-formula", default.exploration_formula)?,
+            first_play_urgency: parse_opt(matches, "fpu", default.first_play_urgency)?,
+            playout_policy: parse_opt(matches, "playout-policy", default.playout_policy)?,
+            expansion_threshold: parse_opt(matches, "expansion-threshold", default.expansion_threshold)?,
+            seed: match matches.opt_str("seed") {
+                None => None,
+                Some(_) => Some(parse_opt(matches, "seed", 0)?),
+            },
+            max_memory: match matches.opt_str("max-memory") {
+                None => None,
+                Some(_) => Some(parse_opt(matches, "max-memory", 0)?),
+            },
+            template_prior: parse_opt(matches, "template-prior", default.template_prior)?,
+        };
+        config.validate().map_err(|e| e.to_string())?;
+        Ok(config)
+    }
+}
+
+/**
+ * Add the `--threads` option. The number of threads isn't part of `SearchConfig`, since it is set
+ * separately with `StarAI::set_threads`.
+ */
+pub fn add_thread_options(opts: &mut Options) {
+    opts.optopt("", "threads", "number of threads to search with (default one for each core)", "N");
+}
+
+/// Read the option added by `add_thread_options`, defaulting to the number of threads the machine
+/// can run in parallel
+pub fn threads_from_matches(matches: &Matches) -> Result<usize, String> {
+    let default = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let threads = parse_opt(matches, "threads", default)?;
+    if threads == 0 {
+        return Err(ConfigError::InvalidParameter("threads").to_string());
+    }
+    Ok(threads)
+}
+
+/// Reasons a game configuration can be rejected
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ConfigError {
+    /// The board size is too small, or too large to be represented
+    UnsupportedSize(usize),
+    /// The configuration differs from the current one, and moves have already been played
+    GameInProgress,
+    /// The search parameter with the given option name is out of range
+    InvalidParameter(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::UnsupportedSize(size) => write!(f, "Unsupported board size {}", size),
+            ConfigError::GameInProgress => write!(f, "Cannot change the rules of a game in progress"),
+            ConfigError::InvalidParameter(name) => write!(f, "Search parameter {} is out of range", name),
+        }
+    }
+}
+
+impl Error for ConfigError {}
@@ -0,0 +1,196 @@
+use std::cmp;
+
+use info::BoardInfo;
+use player::Player;
+
+/// The contribution of a group touching `peri` boundary cells to its owner's score
+pub fn group_value(peri: usize) -> isize {
+    let peri = peri as isize;
+    if peri < 2 {
+        -peri
+    } else {
+        peri - 4
+    }
+}
+
+fn player_index(player: Player) -> usize {
+    match player {
+        Player::First => 0,
+        Player::Second => 1,
+    }
+}
+
+/**
+ * Connected groups of stones, maintained incrementally as stones are added using union-find. Each
+ * group records the number of boundary cells it contains, and the total value of each player's
+ * groups is kept up to date, so the score of a full board can be read off directly.
+ */
+#[derive(Clone)]
+pub struct Groups {
+    /// The player occupying each point
+    owner: Vec<Option<Player>>,
+    /// Parent of each occupied point in the union-find forest
+    parent: Vec<usize>,
+    /// Number of boundary cells in the group, valid for roots only
+    peri: Vec<usize>,
+    /// Sum of `group_value` over the groups of each player
+    totals: [isize; 2],
+    /// Number of boundary cells occupied by each player
+    boundary: [usize; 2],
+    /// Number of boundary cells on the board
+    board_boundary: usize,
+}
+
+impl Groups {
+    pub fn new<const W: usize>(info: &BoardInfo<W>) -> Groups {
+        Groups {
+            owner: vec![None; info.count],
+            parent: (0..info.count).collect(),
+            peri: vec![0; info.count],
+            totals: [0, 0],
+            boundary: [0, 0],
+            board_boundary: (0..info.count).filter(|&i| info.on_boundary(i)).count(),
+        }
+    }
+
+    /// Returns the root of the group containing `point`
+    pub fn find(&mut self, point: usize) -> usize {
+        let mut root = point;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Path compression
+        let mut current = point;
+        while current != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Add a stone for `player` at the empty point `point`, joining it to adjacent groups
+    pub fn add<const W: usize>(&mut self, info: &BoardInfo<W>, point: usize, player: Player) {
+        let index = player_index(player);
+        self.owner[point] = Some(player);
+        let mut peri = if info.on_boundary(point) { 1 } else { 0 };
+        self.boundary[index] += peri;
+        for &other_point in info.adjacencies[point].iter() {
+            if self.owner[other_point] != Some(player) {
+                continue;
+            }
+            let root = self.find(other_point);
+            if root == point {
+                continue;
+            }
+            self.totals[index] -= group_value(self.peri[root]);
+            peri += self.peri[root];
+            self.parent[root] = point;
+        }
+        self.peri[point] = peri;
+        self.totals[index] += group_value(peri);
+    }
+
+    /**
+     * The score of `player` once every point has been played, with komi added for the second
+     * player
+     */
+    pub fn score(&self, player: Player, komi: isize) -> isize {
+        let (own, other, starting_score) = match player {
+            Player::First => (0, 1, -komi),
+            Player::Second => (1, 0, komi),
+        };
+        starting_score + self.totals[own] - self.totals[other]
+    }
+
+    /**
+     * A lower bound on the score of `player` however the remaining points are filled. The groups
+     * not belonging to the player contain all the boundary cells the player doesn't occupy, and
+     * since a group's value is at most its number of boundary cells less 4 when positive, their
+     * total value is at most the number of those cells less 4.
+     */
+    pub fn min_score(&self, player: Player, komi: isize) -> isize {
+        let (own, starting_score) = match player {
+            Player::First => (0, -komi),
+            Player::Second => (1, komi),
+        };
+        let other_boundary = (self.board_boundary - self.boundary[own]) as isize;
+        starting_score + self.totals[own] - cmp::max(other_boundary - 4, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    use board::BoardState;
+    use info::BoardInfo;
+    use patterns::PatternLibrary;
+    use player::PLAYERS;
+    use super::*;
+
+    const KOMI: isize = 1;
+    /// Largest number of empty points whose fillings are searched exhaustively
+    const MAX_EMPTY: usize = 10;
+
+    /// Play `moves` moves at random points, returning the position and its groups
+    fn random_position(info: &BoardInfo<2>, rng: &mut XorShiftRng, moves: usize) -> (BoardState<2>, Groups) {
+        let mut points = (0..info.count).collect::<Vec<_>>();
+        rng.shuffle(&mut points);
+        let mut state = BoardState::new(info);
+        let mut groups = Groups::new(info);
+        for &point in points.iter().take(moves) {
+            groups.add(info, point, state.player_turn());
+            state.add_move(point);
+        }
+        (state, groups)
+    }
+
+    #[test]
+    fn group_values() {
+        let values = (0..7).map(group_value).collect::<Vec<_>>();
+        assert_eq!(values, vec![0, -1, -2, -1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn score_matches_player_score_on_full_boards() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        for size in 2..8 {
+            let info = BoardInfo::<2>::new(size, &PatternLibrary::default());
+            for _ in 0..50 {
+                let (state, groups) = random_position(&info, &mut rng, info.count);
+                for &player in PLAYERS.iter() {
+                    assert_eq!(groups.score(player, KOMI), state.player_score(&info, player, KOMI));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn min_score_bounds_every_filling() {
+        let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+        for size in 3..5 {
+            let info = BoardInfo::<2>::new(size, &PatternLibrary::default());
+            for _ in 0..20 {
+                let empty = rng.gen_range(0, MAX_EMPTY + 1);
+                let (state, groups) = random_position(&info, &mut rng, info.count - empty);
+                let empty_points = (0..info.count).filter(|&i| !state.any(i)).collect::<Vec<_>>();
+                for &player in PLAYERS.iter() {
+                    let min_score = groups.min_score(player, KOMI);
+                    let worst = (0..1u32 << empty_points.len()).map(|filling| {
+                        let mut filled = state;
+                        for (i, &point) in empty_points.iter().enumerate() {
+                            if filling & (1 << i) != 0 {
+                                filled.first_player.set(point);
+                            } else {
+                                filled.second_player.set(point);
+                            }
+                        }
+                        filled.player_score(&info, player, KOMI)
+                    }).min().unwrap();
+                    assert!(worst >= min_score, "{:?} scores {} below the bound {}", player, worst, min_score);
+                }
+            }
+        }
+    }
+}
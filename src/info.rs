@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
 use boardvec::BoardVec;
+use patterns::{PatternLibrary, Cell};
+use templates::{self, Template};
 
 /// Static precomputed data about the board
-pub struct BoardInfo {
+pub struct BoardInfo<const W: usize> {
     /// The length of a side
     pub size: usize,
     /// The maximum value of coordinates + 1
@@ -19,24 +21,33 @@ pub struct BoardInfo {
     pub reverse_coords: HashMap<(usize, usize), usize>,
     /// Patterns to be used in playouts. Patterns are local; for each possible position of the
     /// opponent's last move, we have a list of patterns that could be applicable.
-    pub patterns: Vec<Vec<Pattern>>,
+    pub patterns: Vec<Vec<Pattern<W>>>,
+    /// Every placement of an edge template on the board
+    pub templates: Vec<Template<W>>,
+    /// Patterns answering intrusions into templates, which come first in `patterns`
+    pub template_patterns: Vec<Vec<Pattern<W>>>,
 }
 
 /// A pattern to be used in playouts, e.g. protect bridges
 #[derive(Copy, Clone)]
-pub struct Pattern {
+pub struct Pattern<const W: usize> {
     /// Cells which must be occupied by us for the pattern to be applicable
-    pub ours: BoardVec,
+    pub ours: BoardVec<W>,
+    /// Cells which must be occupied by the opponent for the pattern to be applicable
+    pub theirs: BoardVec<W>,
     /// Cells which must not be occupied for the pattern to be applicable
-    pub empty: BoardVec,
+    pub empty: BoardVec<W>,
     /// Point to play
     pub to_play: usize,
+    /// Strength of the response relative to a random move
+    pub weight: f64,
 }
 
-impl Pattern {
+impl<const W: usize> Pattern<W> {
     /// Check whether pattern is applicable given the points occupied by each player
-    pub fn check(&self, last_player: &BoardVec, current_player: &BoardVec) -> Option<usize> {
-        if !last_player.intersects(&self.empty) && !current_player.intersects(&self.empty) && current_player.contains(&self.ours) {
+    pub fn check(&self, last_player: &BoardVec<W>, current_player: &BoardVec<W>) -> Option<usize> {
+        if !last_player.intersects(&self.empty) && !current_player.intersects(&self.empty) &&
+            current_player.contains(&self.ours) && last_player.contains(&self.theirs) {
             Some(self.to_play)
         } else {
             None
@@ -54,7 +65,7 @@ impl Pattern {
  *    3 O O O O _
  *     4 O O O _ _
  */
-fn in_bounds(size: usize, x: usize, y: usize) -> bool {
+pub fn in_bounds(size: usize, x: usize, y: usize) -> bool {
     let coords_range = size + size - 1;
     if x >= coords_range || y >= coords_range {
         return false;
@@ -63,6 +74,12 @@ fn in_bounds(size: usize, x: usize, y: usize) -> bool {
     s >= size - 1 && s < 3 * size - 2
 }
 
+/// The total number of points on a board with sides of the given length
+pub fn point_count(size: usize) -> usize {
+    let coords_range = size + size - 1;
+    coords_range * coords_range - size * (size - 1)
+}
+
 // FIXME: don't take coords_range
 fn on_boundary(size: usize, coords_range: usize, coords: &Vec<(usize, usize)>, point: usize) -> bool {
     match coords.get(point) {
@@ -78,7 +95,7 @@ fn on_boundary(size: usize, coords_range: usize, coords: &Vec<(usize, usize)>, p
 }
 
 // Offsets of adjacent points *in order* around a point
-const ADJACENT: [(isize, isize); 6] = [(-1, 0), (-1, 1), (0, 1), (1, 0), (1, -1), (0, -1)];
+pub const ADJACENT: [(isize, isize); 6] = [(-1, 0), (-1, 1), (0, 1), (1, 0), (1, -1), (0, -1)];
 
 fn get_adjacent(size: usize, x: usize, y: usize, i: usize) -> Option<(usize, usize)> {
     let (dx, dy) = ADJACENT[i];
@@ -93,10 +110,12 @@ fn get_adjacent(size: usize, x: usize, y: usize, i: usize) -> Option<(usize, usi
     Some((nx as usize, ny as usize))
 }
 
-impl BoardInfo {
-    pub fn new(size: usize) -> BoardInfo {
+impl<const W: usize> BoardInfo<W> {
+    /// Precompute data for a board with sides of length `size`, using the playout patterns from
+    /// `library`
+    pub fn new(size: usize, library: &PatternLibrary) -> BoardInfo<W> {
         let coords_range = size + size - 1;
-        let count = coords_range * coords_range - size * (size - 1);
+        let count = point_count(size);
         let mut coords = Vec::with_capacity(count);
         let mut adj = Vec::with_capacity(count);
         let mut reverse_coords: HashMap<(usize, usize), usize> = HashMap::new();
@@ -120,65 +139,73 @@ impl BoardInfo {
             }
             adj.push(point_adj);
         }
-        let mut patterns = Vec::new();
-        // Bridge patterns
-        for i in 0..count {
-            let (x, y) = coords[i];
-            let mut adjacent1 = get_adjacent(size, x, y, ADJACENT.len() - 2);
-            let mut adjacent2 = get_adjacent(size, x, y, ADJACENT.len() - 1);
-            let mut patterns_for_point = Vec::new();
-            for j in 0..ADJACENT.len() {
-                let adjacent3 = get_adjacent(size, x, y, j);
-                match (adjacent1, adjacent2, adjacent3) {
-                    (Some(coords1), Some(coords2), Some(coords3)) => {
-                        let mut ours = BoardVec::new();
-                        ours.set(*reverse_coords.get(&coords1).unwrap());
-                        ours.set(*reverse_coords.get(&coords3).unwrap());
-                        let mut empty = BoardVec::new();
-                        let to_play = *reverse_coords.get(&coords2).unwrap();
-                        empty.set(to_play);
-                        patterns_for_point.push(Pattern {
-                            ours: ours,
-                            empty: empty,
-                            to_play: to_play,
-                        });
-                    },
-                    (Some(coords1), Some(coords2), None) => {
-                        let point1 = *reverse_coords.get(&coords1).unwrap();
-                        let point2 = *reverse_coords.get(&coords2).unwrap();
-                        // FIXME: we're converting to index and then converting back in on_boundary
-                        let mut empty = BoardVec::new();
-                        let to_play;
-                        if on_boundary(size, coords_range, &coords, point1) {
-                            to_play = point1;
-                            empty.set(point1);
-                        } else {
-                            to_play = point2;
-                            empty.set(point2);
-                        }
-                        patterns_for_point.push(Pattern {
-                            ours: BoardVec::new(),
-                            empty: empty,
-                            to_play: to_play,
-                        });
-                    },
-                    _ => {
-                    },
-                }
-                adjacent1 = adjacent2;
-                adjacent2 = adjacent3;
-            }
-            patterns.push(patterns_for_point);
-        }
-        BoardInfo {
+        let mut info = BoardInfo {
             size: size,
             coords_range: coords_range as usize,
             count: count,
             adjacencies: adj,
             coords: coords,
             reverse_coords: reverse_coords,
-            patterns: patterns,
+            patterns: Vec::new(),
+            templates: Vec::new(),
+            template_patterns: Vec::new(),
+        };
+        info.templates = templates::find_templates(&info);
+        info.template_patterns = templates::template_patterns(&info, &info.templates);
+        info.set_patterns(library);
+        info
+    }
+
+    /// Replace the playout patterns with those from `library`, following the template patterns
+    pub fn set_patterns(&mut self, library: &PatternLibrary) {
+        self.patterns = (0..self.count).map(|point| {
+            let mut patterns_for_point = self.template_patterns[point].clone();
+            for pattern in library.patterns.iter() {
+                for shape in pattern.orientations() {
+                    if let Some(compiled) = self.compile_pattern(point, &shape.cells, pattern.weight) {
+                        patterns_for_point.push(compiled);
+                    }
+                }
+            }
+            patterns_for_point
+        }).collect();
+    }
+
+    /**
+     * Place the cells of a pattern, given by their offsets from the last move, around `point`.
+     * Returns `None` if the cells which must be on the board aren't, or those which must be off
+     * the board aren't.
+     */
+    fn compile_pattern(&self, point: usize, cells: &[((isize, isize), Cell)], weight: f64) -> Option<Pattern<W>> {
+        let (x, y) = self.coords[point];
+        let mut pattern = Pattern {
+            ours: BoardVec::new(),
+            theirs: BoardVec::new(),
+            empty: BoardVec::new(),
+            to_play: 0,
+            weight: weight,
+        };
+        for &((dx, dy), cell) in cells.iter() {
+            let (cell_x, cell_y) = (x as isize + dx, y as isize + dy);
+            let index = if cell_x < 0 || cell_y < 0 {
+                None
+            } else {
+                self.reverse_coords.get(&(cell_x as usize, cell_y as usize)).cloned()
+            };
+            match (cell, index) {
+                (Cell::OffBoard, None) => {},
+                (Cell::OffBoard, Some(_)) | (_, None) => return None,
+                (Cell::Response, Some(index)) => {
+                    pattern.to_play = index;
+                    pattern.empty.set(index);
+                },
+                (Cell::Ours, Some(index)) => pattern.ours.set(index),
+                (Cell::Theirs, Some(index)) => pattern.theirs.set(index),
+                (Cell::Empty, Some(index)) => pattern.empty.set(index),
+                (Cell::OnBoard, Some(_)) => {},
+            }
         }
+        Some(pattern)
     }
 
     /// Whether a given point is on the edge of the board
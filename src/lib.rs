@@ -1,110 +1,269 @@
 extern crate rand;
 extern crate fnv;
+extern crate getopts;
 
 pub mod player;
+pub mod config;
+pub mod record;
+pub mod time_control;
+pub mod patterns;
+pub mod mining;
+pub mod protocol;
 mod boardvec;
 mod info;
 mod statistics;
 mod board;
+mod groups;
+mod policy;
+mod templates;
+
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
 
 use fnv::FnvHashMap;
-use rand::XorShiftRng;
+use rand::{Rng, SeedableRng, XorShiftRng};
 
+use boardvec::BoardVec;
 use info::BoardInfo;
-use board::BoardState;
+use board::{BoardState, format_cells};
 use statistics::Stats;
 use player::Player;
+use config::{GameConfig, SearchConfig, ExplorationFormula, ConfigError};
+use policy::{PlayoutPolicy, ConfiguredPolicy};
+use patterns::PatternLibrary;
 
-const AMAF_PARAMETER: f64 = 1000.;
-const AMAF_LIMIT: f64 = 0.5;
-const INNER_ITERATIONS: u32 = 32;
+/// Winrate used in selection for moves proven to lose, below that of any other move
+const PROVEN_LOSS_WINRATE: f64 = -0.5;
+/// Labels used by `format_ownership`, from points always occupied by the second player to points
+/// always occupied by the first
+const OWNERSHIP_LABELS: [char; 5] = ['O', 'o', '.', 'x', 'X'];
+/// Multiplier for criticality to give the digit shown by `format_ownership`
+const CRITICALITY_SCALE: f64 = 50.;
+/// Label used by `format_groups` once all the letters have been used
+const GROUP_LABEL_OVERFLOW: char = '*';
+/// Number of iterations the pondering thread runs for each search thread each time it takes the
+/// engine lock
+const PONDER_ITERATIONS: usize = 16;
+/// Fraction of the node limit which the table is reduced to when nodes are evicted
+const EVICTION_TARGET: f64 = 0.5;
+/// Bytes in a megabyte, the unit of `SearchConfig::max_memory`
+pub const BYTES_PER_MEGABYTE: usize = 1 << 20;
 
+// Nodes are shared between transpositions, so they don't record the move leading to them
 struct Node {
-    pos: Option<usize>,
     children_created: bool,
     self_visits: u32,
     self_wins: u32,
     visits: u32,
     wins: u32,
+    /// Playouts currently being run below this node by other threads, counted as losses
+    virtual_visits: u32,
+    /**
+     * The winner with perfect play, if known. This is set on the first visit if the game is
+     * already decided, and otherwise once the player to move has a move which is proven to win or
+     * all their moves are proven to lose.
+     */
+    proven: Option<Player>,
+    /// Set while nodes are being evicted for those which are kept
+    marked: bool,
 }
 
 impl Node {
     fn new() -> Node {
         Node {
-            pos: None,
             children_created: false,
             self_visits: 0,
             self_wins: 0,
             visits: 0,
             wins: 0,
+            virtual_visits: 0,
+            proven: None,
+            marked: false,
         }
     }
 
-    fn with_pos(pos: usize) -> Node {
-        Node {
-            pos: Some(pos),
-            children_created: false,
-            self_visits: 0,
-            self_wins: 0,
-            visits: 0,
-            wins: 0,
-        }
+    fn winrate(&self, search_config: &SearchConfig) -> f64 {
+        self.winrate_with_virtual(search_config, self.virtual_visits)
     }
 
-    fn winrate(&self) -> f64 {
-        if self.visits == 0 {
-            return 2.;
+    /// The winrate used in selection, counting `virtual_visits` playouts as losses
+    fn winrate_with_virtual(&self, search_config: &SearchConfig, virtual_visits: u32) -> f64 {
+        let visits = self.visits + virtual_visits;
+        let self_visits = self.self_visits + virtual_visits;
+        if visits == 0 {
+            return search_config.first_play_urgency;
         }
-        let amaf_winrate = (self.wins as f64) / (self.visits as f64);
-        let self_winrate = if self.self_visits == 0 {
+        let amaf_winrate = (self.wins as f64) / (visits as f64);
+        let self_winrate = if self_visits == 0 {
             amaf_winrate
         } else {
-            (self.self_wins as f64) / (self.self_visits as f64)
+            (self.self_wins as f64) / (self_visits as f64)
         };
-        let alpha = AMAF_LIMIT * self.self_visits as f64 / (AMAF_PARAMETER + self.self_visits as f64);
+        let alpha = search_config.amaf_limit * self_visits as f64 /
+            (search_config.amaf_equivalence + self_visits as f64);
         alpha * self_winrate + (1. - alpha) * amaf_winrate
     }
 }
 
-fn create_children(info: &BoardInfo, table: &mut FnvHashMap<BoardState, Node>, state: BoardState) {
+fn create_children<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &mut FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
+    search_config: &SearchConfig,
+) {
+    let answers = template_answers(info, &state);
     for i in 0..info.count {
         if !state.any(i) {
             let mut child_state = state;
             child_state.add_move(i);
             if !table.contains_key(&child_state) {
-                table.insert(child_state, Node::with_pos(i));
+                let mut child = Node::new();
+                if answers.get(i) {
+                    child.visits = search_config.template_prior;
+                    child.wins = search_config.template_prior;
+                }
+                table.insert(child_state, child);
             }
         }
     }
     table.get_mut(&state).unwrap().children_created = true;
 }
 
-fn play(
-    info: &BoardInfo,
-    rng: &mut XorShiftRng,
-    table: &mut FnvHashMap<BoardState, Node>,
-    state: BoardState,
+/// The moves in `state` which answer an intrusion by the opponent into an edge template of the
+/// player to move
+fn template_answers<const W: usize>(info: &BoardInfo<W>, state: &BoardState<W>) -> BoardVec<W> {
+    let (opponent, current) = if state.moves%2 == 0 {
+        (&state.second_player, &state.first_player)
+    } else {
+        (&state.first_player, &state.second_player)
+    };
+    let mut answers = BoardVec::new();
+    for point in 0..info.count {
+        if opponent.get(point) {
+            for pattern in info.template_patterns[point].iter() {
+                if let Some(answer) = pattern.check(opponent, current) {
+                    answers.set(answer);
+                }
+            }
+        }
+    }
+    answers
+}
+
+/**
+ * The exploration term added to the winrate of `child` in selection. Children without playouts of
+ * their own are given the bonus for a single playout, the largest given, so that they are still
+ * preferred over visited children with similar AMAF winrates.
+ */
+fn exploration_bonus(search_config: &SearchConfig, parent_visits: u32, child: &Node) -> f64 {
+    if search_config.exploration == 0. {
+        return 0.;
+    }
+    let log_visits = (parent_visits as f64).ln();
+    let child_visits = cmp::max(child.self_visits + child.virtual_visits, 1) as f64;
+    match search_config.exploration_formula {
+        ExplorationFormula::Ucb1 => {
+            search_config.exploration * (log_visits / child_visits).sqrt()
+        },
+        ExplorationFormula::Ucb1Tuned => {
+            // Upper confidence bound on the variance of the child's results
+            let winrate = child.self_wins as f64 / child_visits;
+            let variance = winrate * (1. - winrate) + (2. * log_visits / child_visits).sqrt();
+            search_config.exploration * (log_visits / child_visits * variance.min(0.25)).sqrt()
+        },
+    }
+}
+
+/// A random number generator giving the same sequence for each seed
+fn seeded_rng(seed: u64) -> XorShiftRng {
+    // XorShiftRng must not be seeded with all zeros
+    XorShiftRng::from_seed([seed as u32 ^ 0x193a6754, (seed >> 32) as u32 ^ 0xa8a7d469, 0x97830e05, 0x113ba7bb])
+}
+
+/// Approximate memory used by each entry in the table, including the hash table's control byte
+fn entry_size<const W: usize>() -> usize {
+    mem::size_of::<(BoardState<W>, Node)>() + 1
+}
+
+/**
+ * The number of nodes which fit within the memory limit of `search_config`. The table allocates a
+ * power of two entries and grows once more than 7/8 of them are used, so this is the capacity of
+ * the largest allocation within the limit, and the table must be allocated with this capacity up
+ * front since growing it would double its size.
+ */
+fn node_limit<const W: usize>(search_config: &SearchConfig) -> Option<usize> {
+    search_config.max_memory.map(|megabytes| {
+        let entries = megabytes.saturating_mul(BYTES_PER_MEGABYTE) / entry_size::<W>();
+        let entries = if entries.is_power_of_two() { entries } else { entries.next_power_of_two() / 2 };
+        entries / 8 * 7
+    })
+}
+
+/// An empty table, allocated with room for as many nodes as the memory limit allows
+fn new_table<const W: usize>(search_config: &SearchConfig) -> FnvHashMap<BoardState<W>, Node> {
+    match node_limit::<W>(search_config) {
+        None => FnvHashMap::default(),
+        Some(limit) => FnvHashMap::with_capacity_and_hasher(limit, Default::default()),
+    }
+}
+
+/// Approximate memory allocated for `table` in bytes, including the entries kept free by its load
+/// factor
+fn table_memory<const W: usize>(table: &FnvHashMap<BoardState<W>, Node>) -> usize {
+    capacity_memory::<W>(table.capacity())
+}
+
+/// Approximate memory allocated for a table with room for `capacity` nodes
+fn capacity_memory<const W: usize>(capacity: usize) -> usize {
+    (capacity + capacity / 7) * entry_size::<W>()
+}
+
+/**
+ * Select a path from `state` to a leaf, adding virtual losses along it so that other threads
+ * prefer different paths. Returns the path, ending with the leaf, and the results of the playouts
+ * if the leaf's result is already known.
+ */
+fn select<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &mut FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
     komi: isize,
-) -> Stats {
-    if state.finished(&info) {
-        // TODO: move this logic to BoardState
-        let winner = if state.is_winner(info, Player::First, komi) {
-            Player::First
-        } else {
-            Player::Second
+    search_config: &SearchConfig,
+) -> (Vec<BoardState<W>>, Option<Stats>) {
+    let playouts = search_config.playouts_per_leaf;
+    let mut state = state;
+    let mut path = Vec::new();
+    loop {
+        path.push(state);
+        let (node_self_visits, node_children_created, node_proven) = {
+            let node = table.get_mut(&state).unwrap();
+            node.virtual_visits += playouts;
+            if node.self_visits == 0 {
+                node.proven = state.decided(info, komi);
+            }
+            (node.self_visits, node.children_created, node.proven)
         };
-        return Stats::single(info, winner, INNER_ITERATIONS);
-    }
-    let (node_self_visits, node_children_created) = {
-        let node = table.get(&state).unwrap();
-        (node.self_visits, node.children_created)
-    };
-    let stats = if node_self_visits == 0 {
-        state.play_random(info, rng, komi, INNER_ITERATIONS)
-    } else {
+        if let Some(winner) = node_proven {
+            return (path, Some(Stats::single(info, winner, playouts)));
+        }
+        if node_self_visits < search_config.expansion_threshold {
+            return (path, None);
+        }
+        // Without room for the children the node stays a leaf until nodes are evicted
+        if !node_children_created && node_limit::<W>(search_config).map_or(false, |limit| {
+            table.len() + info.count - state.moves > limit
+        }) {
+            return (path, None);
+        }
         if !node_children_created {
-            create_children(info, table, state);
+            create_children(info, table, state, search_config);
         }
+        let opponent = state.player_turn().opponent();
         let mut max_winrate = -1.;
         let mut best_child_state = None;
         for i in 0..info.count {
@@ -112,23 +271,68 @@ fn play(
                 let mut child_state = state;
                 child_state.add_move(i);
                 let child = table.get(&child_state).unwrap();
-                let winrate = child.winrate();
+                // Moves proven to lose are only chosen if all moves have been proven to lose but
+                // this hasn't yet been propagated
+                let winrate = if child.proven == Some(opponent) {
+                    PROVEN_LOSS_WINRATE
+                } else {
+                    child.winrate(search_config) + exploration_bonus(search_config, node_self_visits, child)
+                };
                 if winrate > max_winrate {
                     max_winrate = winrate;
                     best_child_state = Some(child_state);
                 }
             }
         }
-        play(info, rng, table, best_child_state.unwrap(), komi)
+        state = best_child_state.unwrap();
+    }
+}
+
+/// Remove the virtual losses added by `select` along `path`, and update it with the playouts
+fn backup<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &mut FnvHashMap<BoardState<W>, Node>,
+    path: Vec<BoardState<W>>,
+    stats: &Stats,
+    search_config: &SearchConfig,
+) {
+    for path_state in path.into_iter().rev() {
+        table.get_mut(&path_state).unwrap().virtual_visits -= search_config.playouts_per_leaf;
+        update(info, table, path_state, stats);
+    }
+}
+
+/**
+ * Run one iteration of the search from `state`. The path to a leaf is selected while holding the
+ * table lock, the playouts from the leaf are run without the lock, and then the nodes on the path
+ * are updated.
+ */
+fn play<const W: usize, P: PlayoutPolicy<W>>(
+    info: &BoardInfo<W>,
+    policy: &P,
+    rng: &mut XorShiftRng,
+    table: &Mutex<FnvHashMap<BoardState<W>, Node>>,
+    state: BoardState<W>,
+    komi: isize,
+    search_config: &SearchConfig,
+) -> Stats {
+    let (path, terminal_stats) = select(info, &mut table.lock().unwrap(), state, komi, search_config);
+    let stats = match terminal_stats {
+        Some(stats) => stats,
+        None => path.last().unwrap().play_random(info, policy, rng, komi, search_config.playouts_per_leaf),
     };
-    update(info, table, state, &stats);
+    backup(info, &mut table.lock().unwrap(), path, &stats, search_config);
     stats
 }
 
-fn update(
-    info: &BoardInfo,
-    table: &mut FnvHashMap<BoardState, Node>,
-    state: BoardState,
+/**
+ * Update the statistics of the node for `state` and the AMAF statistics of its children with the
+ * results of the playouts in `stats`, and mark the node as proven if its children prove it
+ */
+fn update<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &mut FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
     stats: &Stats,
 ) {
     let is_first_player = state.moves%2 != 0;
@@ -148,6 +352,9 @@ fn update(
     if !children_created {
         return;
     }
+    let to_play = state.player_turn();
+    let mut any_win = false;
+    let mut all_lose = true;
     for i in 0..info.count {
         if !state.any(i) {
             let mut child_state = state;
@@ -160,102 +367,1071 @@ fn update(
                 child.visits += stats.point_stats[i].p2;
                 child.wins += stats.point_stats[i].p2_wins;
             }
+            match child.proven {
+                Some(winner) if winner == to_play => any_win = true,
+                Some(_) => {},
+                None => all_lose = false,
+            }
+        }
+    }
+    let node = table.get_mut(&state).unwrap();
+    if any_win {
+        node.proven = Some(to_play);
+    } else if all_lose {
+        node.proven = Some(to_play.opponent());
+    }
+}
+
+/**
+ * Set `marked` on the nodes in the tree below `state`, following the children of expanded nodes.
+ * Nodes are marked rather than collected into a set, since a copy of the tree wouldn't fit within
+ * the memory limit.
+ */
+fn mark_reachable<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &mut FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
+) {
+    table.get_mut(&state).unwrap().marked = true;
+    let mut stack = vec![state];
+    while let Some(state) = stack.pop() {
+        if !table.get(&state).unwrap().children_created {
+            continue;
+        }
+        for i in 0..info.count {
+            if !state.any(i) {
+                let mut child_state = state;
+                child_state.add_move(i);
+                let child = table.get_mut(&child_state).unwrap();
+                if !child.marked {
+                    child.marked = true;
+                    stack.push(child_state);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Reduce the tree below `state` to at most `target` nodes, returning the number of nodes removed.
+ * Expanded nodes keep their children if they have at least as many playouts as the least visited
+ * node whose children still fit, and the rest become leaves again. Nodes which can then no longer
+ * be reached from `state` are removed.
+ */
+fn evict<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &mut FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
+    target: usize,
+) -> usize {
+    mark_reachable(info, table, state);
+    // Transpositions are counted once for each parent, so this overestimates the size
+    let kept_nodes = |table: &FnvHashMap<BoardState<W>, Node>, visits: u32| {
+        1 + table.iter().filter(|&(_, node)| node.marked && node.children_created && node.self_visits >= visits)
+            .map(|(node_state, _)| info.count - node_state.moves).sum::<usize>()
+    };
+    // The fewest playouts a node can have and keep its children, found by bisection to avoid
+    // collecting the expanded nodes
+    let mut low = 0;
+    let mut high = table.values().map(|node| node.self_visits).max().unwrap().saturating_add(1);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if kept_nodes(table, middle) <= target {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    for node in table.values_mut() {
+        if node.marked && node.self_visits < low {
+            node.children_created = false;
         }
+        node.marked = false;
     }
+    mark_reachable(info, table, state);
+    let count = table.len();
+    table.retain(|_, node| mem::replace(&mut node.marked, false));
+    count - table.len()
+}
+
+/// The order in which moves are preferred by `best_move`, where `to_play` is the player to move
+fn move_rank(child: &Node, to_play: Player) -> (u8, u32) {
+    let proof_rank = match child.proven {
+        Some(winner) if winner == to_play => 2,
+        Some(_) => 0,
+        None => 1,
+    };
+    (proof_rank, child.self_visits)
 }
 
-fn best_move(
-    info: &BoardInfo,
-    table: &FnvHashMap<BoardState, Node>,
-    state: BoardState,
+/**
+ * Returns the move with the most visits, preferring moves proven to win and avoiding moves proven
+ * to lose
+ */
+fn best_move<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
 ) -> usize {
-    let mut most_visits = 0;
+    let to_play = state.player_turn();
+    let mut best_rank = (0, 0);
     let mut best_move = None;
     for i in 0..info.count {
         if !state.any(i) {
             let mut child_state = state;
             child_state.add_move(i);
-            let child = table.get(&child_state).unwrap();
-            if let None = best_move {
-                most_visits = child.self_visits;
-                best_move = Some(child.pos);
-            } else if child.self_visits > most_visits {
-                most_visits = child.self_visits;
-                best_move = Some(child.pos);
+            let rank = move_rank(table.get(&child_state).unwrap(), to_play);
+            if best_move.is_none() || rank > best_rank {
+                best_rank = rank;
+                best_move = Some(i);
             }
         }
     }
-    best_move.unwrap().unwrap()
+    best_move.unwrap()
 }
 
-pub struct StarAI {
-    info: BoardInfo,
-    state: BoardState,
-    table: FnvHashMap<BoardState, Node>,
+/// Returns the moves from `state` chosen by `best_move` while the positions have children
+fn principal_variation<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
+) -> Vec<usize> {
+    let mut state = state;
+    let mut variation = Vec::new();
+    while table.get(&state).map_or(false, |node| node.children_created) {
+        let index = best_move(info, table, state);
+        variation.push(index);
+        state.add_move(index);
+    }
+    variation
+}
+
+/// Returns the visits to the root, the index of the most visited child and its lead in visits over
+/// the next most visited child, or `None` if the children haven't been created yet
+fn visit_lead<const W: usize>(
+    info: &BoardInfo<W>,
+    table: &FnvHashMap<BoardState<W>, Node>,
+    state: BoardState<W>,
+) -> Option<(u32, usize, u32)> {
+    let root = table.get(&state).unwrap();
+    if !root.children_created {
+        return None;
+    }
+    let mut best = None;
+    let mut second_visits = 0;
+    for i in 0..info.count {
+        if !state.any(i) {
+            let mut child_state = state;
+            child_state.add_move(i);
+            let visits = table.get(&child_state).unwrap().self_visits;
+            match best {
+                Some((_, best_visits)) if visits <= best_visits => {
+                    second_visits = second_visits.max(visits);
+                },
+                Some((_, best_visits)) => {
+                    second_visits = best_visits;
+                    best = Some((i, visits));
+                },
+                None => {
+                    best = Some((i, visits));
+                },
+            }
+        }
+    }
+    best.map(|(i, visits)| (root.self_visits, i, visits - second_visits))
+}
+
+/// The largest board size supported by `StarAI`
+pub const MAX_SIZE: usize = 12;
+
+/// Search state for a board whose positions fit in a `BoardVec` of `W` words, running playouts
+/// with the policy `P`
+struct Search<const W: usize, P: PlayoutPolicy<W>> {
+    config: GameConfig,
+    search_config: SearchConfig,
+    policy: P,
+    info: BoardInfo<W>,
+    state: BoardState<W>,
+    table: Mutex<FnvHashMap<BoardState<W>, Node>>,
     rng: XorShiftRng,
+    threads: usize,
+    /// Indices of the moves played so far
+    history: Vec<usize>,
+    /// Indices of moves which have been undone, most recently undone last
+    undone: Vec<usize>,
+    /// Scores of the first and second players in the current position
+    scores: (isize, isize),
+    /// Results of the playouts run from the current position
+    position_stats: Stats,
+    winner: Option<Player>,
+    /// Number of nodes evicted to stay within the memory limit
+    evicted: usize,
 }
 
-// TODO: move implementations into here
-// TODO: pass komi in on construction
-// TODO: rename to something else
-impl StarAI {
-    pub fn new(size: usize) -> StarAI {
-        let info = BoardInfo::new(size);
+impl<const W: usize, P: PlayoutPolicy<W>> Search<W, P> {
+    fn new(config: GameConfig, search_config: SearchConfig, policy: P, patterns: &PatternLibrary) -> Search<W, P> {
+        let info = BoardInfo::new(config.size, patterns);
         let state = BoardState::new(&info);
-        let mut table = FnvHashMap::default();
+        let mut table = new_table(&search_config);
         table.insert(state, Node::new());
-        StarAI {
+        let scores = (
+            state.player_score(&info, Player::First, config.komi),
+            state.player_score(&info, Player::Second, config.komi),
+        );
+        let winner = state.decided(&info, config.komi);
+        let position_stats = Stats::new(&info);
+        Search {
+            config: config,
+            search_config: search_config,
+            policy: policy,
             info: info,
             state: state,
-            table: table,
-            rng: rand::weak_rng(),
+            table: Mutex::new(table),
+            rng: search_config.seed.map_or_else(rand::weak_rng, seeded_rng),
+            threads: 1,
+            history: Vec::new(),
+            undone: Vec::new(),
+            scores: scores,
+            position_stats: position_stats,
+            winner: winner,
+            evicted: 0,
         }
     }
 
-    // TODO: figure out how to thread komi through everything
-    pub fn calculate(&mut self, iterations: usize, komi: isize) {
-        for _ in 0..iterations {
-            play(&self.info, &mut self.rng, &mut self.table, self.state, komi);
+    fn calculate(&mut self, iterations: usize) {
+        self.evict_if_full();
+        let komi = self.config.komi;
+        let search_config = self.search_config;
+        if self.threads <= 1 {
+            for _ in 0..iterations {
+                let stats = play(&self.info, &self.policy, &mut self.rng, &self.table, self.state, komi, &search_config);
+                self.position_stats.merge(&stats);
+            }
+            return;
+        }
+        if self.search_config.seed.is_some() {
+            self.calculate_lockstep(iterations);
+            return;
+        }
+        // Each thread takes iterations from a shared count until none are left
+        let remaining = AtomicUsize::new(iterations);
+        let rngs = (0..self.threads).map(|_| self.rng.gen::<XorShiftRng>()).collect::<Vec<_>>();
+        let (info, policy, table, state, remaining, search_config) = (&self.info, &self.policy, &self.table, self.state, &remaining, &search_config);
+        let thread_stats = thread::scope(|scope| {
+            let handles = rngs.into_iter().map(|mut rng| {
+                scope.spawn(move || {
+                    let mut thread_stats = Stats::new(info);
+                    while remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok() {
+                        thread_stats.merge(&play(info, policy, &mut rng, table, state, komi, search_config));
+                    }
+                    thread_stats
+                })
+            }).collect::<Vec<_>>();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+        });
+        for stats in thread_stats.iter() {
+            self.position_stats.merge(stats);
         }
     }
 
-    pub fn best_move(&self) -> (usize, usize) {
-        let index = best_move(&self.info, &self.table, self.state);
+    /**
+     * Run iterations on several threads reproducibly. Paths are selected and updated in batches of
+     * one per thread in a fixed order, and only the playouts are run in parallel, with each thread
+     * using a random number generator derived from the seeded one.
+     */
+    fn calculate_lockstep(&mut self, iterations: usize) {
+        let komi = self.config.komi;
+        let search_config = self.search_config;
+        let rngs = (0..self.threads).map(|_| self.rng.gen::<XorShiftRng>()).collect::<Vec<_>>();
+        let (info, policy, table, state, search_config) = (&self.info, &self.policy, &self.table, self.state, &search_config);
+        let position_stats = &mut self.position_stats;
+        thread::scope(|scope| {
+            let workers = rngs.into_iter().map(|mut rng| {
+                let (leaf_sender, leaf_receiver) = mpsc::channel::<BoardState<W>>();
+                let (stats_sender, stats_receiver) = mpsc::channel();
+                scope.spawn(move || {
+                    for leaf in leaf_receiver {
+                        let stats = leaf.play_random(info, policy, &mut rng, komi, search_config.playouts_per_leaf);
+                        stats_sender.send(stats).unwrap();
+                    }
+                });
+                (leaf_sender, stats_receiver)
+            }).collect::<Vec<_>>();
+            let mut remaining = iterations;
+            while remaining > 0 {
+                let batch = cmp::min(remaining, workers.len());
+                let selections = {
+                    let mut table = table.lock().unwrap();
+                    workers.iter().take(batch).map(|&(ref leaf_sender, _)| {
+                        let (path, terminal_stats) = select(info, &mut table, state, komi, search_config);
+                        if terminal_stats.is_none() {
+                            leaf_sender.send(*path.last().unwrap()).unwrap();
+                        }
+                        (path, terminal_stats)
+                    }).collect::<Vec<_>>()
+                };
+                let mut table = table.lock().unwrap();
+                for (&(_, ref stats_receiver), (path, terminal_stats)) in workers.iter().zip(selections) {
+                    let stats = terminal_stats.unwrap_or_else(|| stats_receiver.recv().unwrap());
+                    backup(info, &mut table, path, &stats, search_config);
+                    position_stats.merge(&stats);
+                }
+                remaining -= batch;
+            }
+            // The workers stop once their senders are dropped at the end of this closure
+        });
+    }
+
+    /**
+     * Evict nodes if the table is close enough to the node limit that an expansion may not fit, and
+     * reallocate the table if its capacity doesn't match the limit, e.g. because the limit changed
+     */
+    fn evict_if_full(&mut self) {
+        let limit = match node_limit::<W>(&self.search_config) {
+            None => return,
+            Some(limit) => limit,
+        };
+        let table = self.table.get_mut().unwrap();
+        if table.len() + self.info.count > limit {
+            let target = (limit as f64 * EVICTION_TARGET) as usize;
+            self.evicted += evict(&self.info, table, self.state, target);
+        }
+        if table.capacity() != limit {
+            // The nodes are kept in a vector while the table is reallocated, so only as many are
+            // kept as fit beside the new table within the limit
+            let memory = self.search_config.max_memory.unwrap().saturating_mul(BYTES_PER_MEGABYTE);
+            let spare = memory.saturating_sub(capacity_memory::<W>(limit));
+            let target = spare / mem::size_of::<(BoardState<W>, Node)>();
+            if table.len() > target {
+                self.evicted += evict(&self.info, table, self.state, target);
+            }
+            let nodes = table.drain().collect::<Vec<_>>();
+            // The old table is freed before the new one is allocated
+            *table = FnvHashMap::default();
+            *table = new_table(&self.search_config);
+            table.extend(nodes);
+        }
+    }
+
+    fn table_usage(&self) -> TableUsage {
+        let table = self.table.lock().unwrap();
+        let usage = TableUsage {
+            nodes: table.len(),
+            memory: table_memory(&table),
+            node_limit: node_limit::<W>(&self.search_config),
+            evicted: self.evicted,
+        };
+        debug_assert!(self.search_config.max_memory.map_or(true, |megabytes| {
+            usage.memory <= megabytes * BYTES_PER_MEGABYTE
+        }), "Search tree uses {} bytes, over the memory limit", usage.memory);
+        usage
+    }
+
+    fn best_move(&self) -> (usize, usize) {
+        let mut table = self.table.lock().unwrap();
+        // With a high expansion threshold the root may not have been expanded yet, and its
+        // children are then chosen between using their priors
+        if !table.get(&self.state).unwrap().children_created {
+            create_children(&self.info, &mut table, self.state, &self.search_config);
+        }
+        let index = best_move(&self.info, &table, self.state);
         self.info.coords[index]
     }
 
-    pub fn add_move(&mut self, x: usize, y: usize) {
-        self.state.add_move(*self.info.reverse_coords.get(&(x, y)).unwrap());
-        self.table = FnvHashMap::default();
-        self.table.insert(self.state, Node::new());
+    fn progress(&self) -> Option<SearchProgress> {
+        visit_lead(&self.info, &self.table.lock().unwrap(), self.state).map(|(visits, index, lead)| {
+            SearchProgress {
+                visits: visits,
+                best_move: self.info.coords[index],
+                lead: lead,
+            }
+        })
     }
 
-    pub fn size(&self) -> usize {
-        self.info.size
+    fn analysis(&self, count: usize) -> Vec<MoveAnalysis> {
+        let table = self.table.lock().unwrap();
+        if !table.get(&self.state).unwrap().children_created {
+            return Vec::new();
+        }
+        let to_play = self.state.player_turn();
+        let mut children = Vec::new();
+        for i in 0..self.info.count {
+            if !self.state.any(i) {
+                let mut child_state = self.state;
+                child_state.add_move(i);
+                children.push((i, child_state, table.get(&child_state).unwrap()));
+            }
+        }
+        // Stable, so ties are broken by index as in `best_move`
+        children.sort_by_key(|&(_, _, child)| cmp::Reverse(move_rank(child, to_play)));
+        children.iter().take(count).map(|&(index, child_state, child)| {
+            let mut variation = vec![index];
+            variation.extend(principal_variation(&self.info, &table, child_state));
+            MoveAnalysis {
+                point: self.info.coords[index],
+                visits: child.self_visits,
+                amaf_visits: child.visits,
+                winrate: if child.visits == 0 {
+                    None
+                } else {
+                    Some(child.winrate_with_virtual(&self.search_config, 0))
+                },
+                proven_winner: child.proven,
+                variation: variation.iter().map(|&i| self.info.coords[i]).collect(),
+            }
+        }).collect()
     }
 
-    pub fn print_board(&self) {
+    fn ownership(&self) -> Vec<PointOwnership> {
+        let stats = &self.position_stats;
+        if stats.count == 0 {
+            return Vec::new();
+        }
+        let first_winrate = stats.first_player_wins as f64 / stats.count as f64;
+        stats.point_stats.iter().enumerate().filter_map(|(i, point_stats)| {
+            let samples = point_stats.p1 + point_stats.p2;
+            if samples == 0 {
+                return None;
+            }
+            let first_owner = point_stats.p1 as f64 / samples as f64;
+            let owner_wins = (point_stats.p1_wins + point_stats.p2_wins) as f64 / samples as f64;
+            // How much more often the owner wins than if ownership were independent of winning
+            let independent = first_owner * first_winrate + (1. - first_owner) * (1. - first_winrate);
+            Some(PointOwnership {
+                point: self.info.coords[i],
+                samples: samples,
+                first_owner: first_owner,
+                criticality: owner_wins - independent,
+            })
+        }).collect()
+    }
+
+    fn format_ownership(&self) -> String {
+        let mut owners = vec![' '; self.info.count];
+        let mut criticalities = vec![' '; self.info.count];
+        for ownership in self.ownership() {
+            let i = self.info.reverse_coords[&ownership.point];
+            owners[i] = OWNERSHIP_LABELS[((ownership.first_owner * OWNERSHIP_LABELS.len() as f64) as usize).min(OWNERSHIP_LABELS.len() - 1)];
+            let level = (ownership.criticality * CRITICALITY_SCALE).clamp(0., 9.) as u32;
+            criticalities[i] = std::char::from_digit(level, 10).unwrap();
+        }
+        format!(
+            "Ownership\n{}Criticality\n{}",
+            format_cells(&self.info, |i| owners[i]),
+            format_cells(&self.info, |i| criticalities[i]),
+        )
+    }
+
+    fn proven_winner(&self) -> Option<Player> {
+        self.table.lock().unwrap().get(&self.state).unwrap().proven
+    }
+
+    fn empty_cells(&self) -> usize {
+        self.info.count - self.state.moves
+    }
+
+    fn add_move(&mut self, x: usize, y: usize) -> Result<(), MoveError> {
+        if self.winner().is_some() {
+            return Err(MoveError::GameOver);
+        }
+        let index = match self.info.reverse_coords.get(&(x, y)) {
+            None => return Err(MoveError::OffBoard),
+            Some(&index) => index,
+        };
+        if self.state.any(index) {
+            return Err(MoveError::Occupied);
+        }
+        self.play_move(index);
+        self.undone.clear();
+        Ok(())
+    }
+
+    fn play_move(&mut self, index: usize) {
+        self.state.add_move(index);
+        self.history.push(index);
+        // Keep the subtree below the new position, dropping everything that can no longer be
+        // reached
+        let state = self.state;
+        let table = self.table.get_mut().unwrap();
+        table.retain(|child_state, _| child_state.follows(&state));
+        table.entry(state).or_insert_with(Node::new);
+        self.update_scores();
+    }
+
+    fn update_scores(&mut self) {
+        self.scores = (
+            self.state.player_score(&self.info, Player::First, self.config.komi),
+            self.state.player_score(&self.info, Player::Second, self.config.komi),
+        );
+        self.winner = self.state.decided(&self.info, self.config.komi);
+        // Playouts from the previous position aren't representative of the new one
+        self.position_stats = Stats::new(&self.info);
+    }
+
+    fn undo(&mut self) -> Option<(usize, usize)> {
+        let index = self.history.pop()?;
+        self.state.remove_move(index);
+        self.undone.push(index);
+        // The table only holds the subtree below the undone move, which can still be reached from
+        // the previous position, while the previous position itself starts again as a new leaf
+        self.table.get_mut().unwrap().entry(self.state).or_insert_with(Node::new);
+        self.update_scores();
+        Some(self.info.coords[index])
+    }
+
+    fn redo(&mut self) -> Option<(usize, usize)> {
+        let index = self.undone.pop()?;
+        self.play_move(index);
+        Some(self.info.coords[index])
+    }
+
+    fn history(&self) -> Vec<(usize, usize)> {
+        self.history.iter().map(|&index| self.info.coords[index]).collect()
+    }
+
+    fn config(&self) -> GameConfig {
+        self.config
+    }
+
+    fn search_config(&self) -> SearchConfig {
+        self.search_config
+    }
+
+    fn set_search_config(&mut self, search_config: SearchConfig) {
+        if search_config.seed != self.search_config.seed {
+            self.rng = search_config.seed.map_or_else(rand::weak_rng, seeded_rng);
+        }
+        self.search_config = search_config;
+    }
+
+    fn set_policy(&mut self, policy: P) {
+        self.policy = policy;
+    }
+
+    fn set_patterns(&mut self, patterns: &PatternLibrary) {
+        self.info.set_patterns(patterns);
+    }
+
+    fn moves(&self) -> usize {
+        self.state.moves
+    }
+
+    fn print_board(&self) {
         self.state.print_board(&self.info);
     }
 
-    pub fn winner(&self, komi: isize) -> Option<Player> {
-        for player in vec![Player::First, Player::Second] {
-            if self.state.player_score(&self.info, player, komi) > 0 {
-                return Some(player);
+    fn format_board(&self) -> String {
+        self.state.format_board(&self.info)
+    }
+
+    fn score(&self, player: Player) -> isize {
+        match player {
+            Player::First => self.scores.0,
+            Player::Second => self.scores.1,
+        }
+    }
+
+    fn score_breakdown(&self, player: Player) -> Vec<GroupScore> {
+        let opponent = player.opponent();
+        let coords = |points: &Vec<usize>| points.iter().map(|&i| self.info.coords[i]).collect();
+        self.state.score_groups(&self.info, player).iter().map(|group| {
+            GroupScore {
+                owner: if group.own { player } else { opponent },
+                points: coords(&group.points),
+                boundary: coords(&group.boundary),
+                value: groups::group_value(group.boundary.len()),
+            }
+        }).collect()
+    }
+
+    fn format_groups(&self, player: Player) -> String {
+        let score_groups = self.score_breakdown(player);
+        let mut labels = vec![' '; self.info.count];
+        let (mut own_labels, mut other_labels) = (b'A'..=b'Z', b'a'..=b'z');
+        let mut descriptions = String::new();
+        for group in score_groups.iter() {
+            let own = group.owner == player;
+            let label = if own { own_labels.next() } else { other_labels.next() };
+            let label = label.map_or(GROUP_LABEL_OVERFLOW, |label| label as char);
+            for point in group.points.iter() {
+                labels[self.info.reverse_coords[point]] = label;
+            }
+            descriptions.push_str(&format!(
+                "{} {:?}: {} points, {} boundary cells, {:+}\n",
+                label,
+                group.owner,
+                group.points.len(),
+                group.boundary.len(),
+                if own { group.value } else { -group.value },
+            ));
+        }
+        let komi = match player {
+            Player::First => -self.config.komi,
+            Player::Second => self.config.komi,
+        };
+        format!(
+            "{}{}Komi: {:+}\nScore for {:?}: {}\n",
+            format_cells(&self.info, |i| labels[i]),
+            descriptions,
+            komi,
+            player,
+            self.score(player),
+        )
+    }
+
+    fn winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    fn player_turn(&self) -> Player {
+        self.state.player_turn()
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    fn threads(&self) -> usize {
+        self.threads
+    }
+}
+
+/// A search specialised to the number of words needed to represent the board
+enum Engine {
+    Small(Search<2, ConfiguredPolicy>),
+    Medium(Search<4, ConfiguredPolicy>),
+    Large(Search<7, ConfiguredPolicy>),
+}
+
+impl Engine {
+    fn new(config: GameConfig, search_config: SearchConfig, patterns: &PatternLibrary) -> Result<Engine, ConfigError> {
+        config.validate()?;
+        search_config.validate()?;
+        let policy = ConfiguredPolicy::new(search_config.playout_policy);
+        Ok(match boardvec::words_for(info::point_count(config.size)) {
+            0..=2 => Engine::Small(Search::new(config, search_config, policy, patterns)),
+            3..=4 => Engine::Medium(Search::new(config, search_config, policy, patterns)),
+            _ => Engine::Large(Search::new(config, search_config, policy, patterns)),
+        })
+    }
+}
+
+/// Evaluates `$body` with `$search` bound to the search inside the engine `$engine`
+macro_rules! with_search {
+    ($engine:expr, $search:ident => $body:expr) => {
+        match $engine {
+            Engine::Small($search) => $body,
+            Engine::Medium($search) => $body,
+            Engine::Large($search) => $body,
+        }
+    }
+}
+
+/// A connected group of points counted when scoring, as returned by `StarAI::score_breakdown`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GroupScore {
+    /// The player the group is counted for. When scoring a player, every point which they don't
+    /// occupy is counted for their opponent.
+    pub owner: Player,
+    pub points: Vec<(usize, usize)>,
+    /// The points of the group on the boundary of the board
+    pub boundary: Vec<(usize, usize)>,
+    /// The contribution of the group to its owner's score: its number of boundary cells less 4,
+    /// or minus the number of boundary cells if it touches fewer than 2
+    pub value: isize,
+}
+
+/// Who occupied a point at the end of playouts from the current position, as returned by
+/// `StarAI::ownership`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PointOwnership {
+    pub point: (usize, usize),
+    /// Number of playouts in which the point was played before the game was decided
+    pub samples: u32,
+    /// Proportion of those playouts in which the first player occupied the point
+    pub first_owner: f64,
+    /**
+     * How much more often the player occupying the point won than would be expected if
+     * occupying it made no difference, from -1 to 1. Points which decide the game have high
+     * criticality.
+     */
+    pub criticality: f64,
+}
+
+/// A candidate move and the search's evaluation of it, as returned by `StarAI::analysis`
+#[derive(Clone, PartialEq, Debug)]
+pub struct MoveAnalysis {
+    pub point: (usize, usize),
+    /// Number of playouts run after this move
+    pub visits: u32,
+    /// Number of playouts in which this point was played by the player to move (AMAF)
+    pub amaf_visits: u32,
+    /// The winrate for the player to move, blending the winrate of the move's own playouts and
+    /// its AMAF winrate as in selection, or `None` if there are no playouts for the move
+    pub winrate: Option<f64>,
+    /// The winner if it has been proven after this move
+    pub proven_winner: Option<Player>,
+    /// The expected continuation, starting with this move and following the best move from each
+    /// position as long as it has been expanded
+    pub variation: Vec<(usize, usize)>,
+}
+
+/// Summary of the search from the current position, used to decide when to stop thinking
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SearchProgress {
+    /// Number of playouts run from the current position
+    pub visits: u32,
+    /// The move which would be returned by `best_move`
+    pub best_move: (usize, usize),
+    /// How many more playouts have been run for the best move than for any other move
+    pub lead: u32,
+}
+
+/// The size of the search tree
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TableUsage {
+    /// Number of positions in the tree
+    pub nodes: usize,
+    /// Approximate memory allocated for the tree in bytes
+    pub memory: usize,
+    /// The largest number of positions allowed by the memory limit, if there is one
+    pub node_limit: Option<usize>,
+    /// Total number of positions evicted to stay within the memory limit
+    pub evicted: usize,
+}
+
+/// Reasons a move can be rejected
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    /// The coordinates are not a point on the board
+    OffBoard,
+    /// A player has already played at the point
+    Occupied,
+    /// The winner has already been decided
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveError::OffBoard => write!(f, "Move is not on the board"),
+            MoveError::Occupied => write!(f, "Point is already occupied"),
+            MoveError::GameOver => write!(f, "Game is already over"),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+/// A background search running on the opponent's time
+struct Ponder {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<usize>,
+}
+
+pub struct StarAI {
+    engine: Arc<Mutex<Engine>>,
+    ponder: Option<Ponder>,
+    /// Patterns for playouts, kept for when the engine is recreated
+    patterns: PatternLibrary,
+}
+
+// TODO: move implementations into here
+// TODO: rename to something else
+impl StarAI {
+    /// Create an AI for the given rules. The board size must be between 2 and `MAX_SIZE`.
+    pub fn new(config: GameConfig) -> StarAI {
+        StarAI::with_search_config(config, SearchConfig::default())
+    }
+
+    /// Create an AI for the given rules, searching with the given parameters
+    pub fn with_search_config(config: GameConfig, search_config: SearchConfig) -> StarAI {
+        let patterns = PatternLibrary::default();
+        let engine = Engine::new(config, search_config, &patterns).unwrap_or_else(|e| {
+            panic!("{}", e);
+        });
+        StarAI {
+            engine: Arc::new(Mutex::new(engine)),
+            ponder: None,
+            patterns: patterns,
+        }
+    }
+
+    fn engine(&self) -> MutexGuard<'_, Engine> {
+        self.engine.lock().unwrap()
+    }
+
+    pub fn config(&self) -> GameConfig {
+        with_search!(&*self.engine(), search => search.config())
+    }
+
+    /**
+     * Change the rules of the game. This is only allowed before any moves have been played, and
+     * discards any search results; afterwards any configuration differing from the current one is
+     * rejected.
+     */
+    pub fn set_config(&mut self, config: GameConfig) -> Result<(), ConfigError> {
+        let mut engine = self.engine();
+        let (current, search_config, moves, threads) = with_search!(&*engine, search => {
+            (search.config(), search.search_config(), search.moves(), search.threads())
+        });
+        if config == current {
+            return Ok(());
+        }
+        if moves > 0 {
+            return Err(ConfigError::GameInProgress);
+        }
+        *engine = Engine::new(config, search_config, &self.patterns)?;
+        with_search!(&mut *engine, search => search.set_threads(threads));
+        Ok(())
+    }
+
+    pub fn search_config(&self) -> SearchConfig {
+        with_search!(&*self.engine(), search => search.search_config())
+    }
+
+    /**
+     * Change the parameters of the search. Search results so far are kept, and the random number
+     * generator is only reseeded if the seed changes.
+     */
+    pub fn set_search_config(&mut self, search_config: SearchConfig) -> Result<(), ConfigError> {
+        search_config.validate()?;
+        with_search!(&mut *self.engine(), search => {
+            search.set_search_config(search_config);
+            search.set_policy(ConfiguredPolicy::new(search_config.playout_policy));
+        });
+        Ok(())
+    }
+
+    /// Replace the patterns used in playouts, keeping the search results so far
+    pub fn set_patterns(&mut self, patterns: PatternLibrary) {
+        with_search!(&mut *self.engine(), search => search.set_patterns(&patterns));
+        self.patterns = patterns;
+    }
+
+    /// Start a new game with the same rules, discarding all moves and search results
+    pub fn clear(&mut self) {
+        let mut engine = self.engine();
+        let (config, search_config, threads) = with_search!(&*engine, search => {
+            (search.config(), search.search_config(), search.threads())
+        });
+        *engine = Engine::new(config, search_config, &self.patterns).unwrap();
+        with_search!(&mut *engine, search => search.set_threads(threads));
+    }
+
+    pub fn calculate(&mut self, iterations: usize) {
+        with_search!(&mut *self.engine(), search => search.calculate(iterations))
+    }
+
+    /**
+     * Start searching the current position on a background thread until `stop_pondering` is
+     * called, or until the game is over or its result has been proven. Moves may still be added
+     * while pondering, in which case the search continues from the subtree below the new position.
+     */
+    pub fn start_pondering(&mut self) {
+        if self.ponder.is_some() {
+            return;
+        }
+        let engine = self.engine.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut iterations = 0;
+            while !thread_stop.load(Ordering::Relaxed) {
+                // Threads are started for each call to `calculate`, so enough iterations are run
+                // to keep them all busy
+                let ran = with_search!(&mut *engine.lock().unwrap(), search => {
+                    if search.winner().is_some() || search.proven_winner().is_some() {
+                        None
+                    } else {
+                        let batch = PONDER_ITERATIONS * search.threads();
+                        search.calculate(batch);
+                        Some(batch)
+                    }
+                });
+                match ran {
+                    None => break,
+                    Some(batch) => iterations += batch,
+                }
+                // Give the main thread a chance to take the lock, e.g. to add the opponent's move
+                thread::yield_now();
             }
+            iterations
+        });
+        self.ponder = Some(Ponder {
+            stop: stop,
+            handle: handle,
+        });
+    }
+
+    /**
+     * Stop the background search, returning the number of iterations it ran. Does nothing if not
+     * currently pondering.
+     */
+    pub fn stop_pondering(&mut self) -> usize {
+        match self.ponder.take() {
+            None => 0,
+            Some(ponder) => {
+                ponder.stop.store(true, Ordering::Relaxed);
+                ponder.handle.join().unwrap()
+            },
         }
-        None
     }
 
-    pub fn score(&self, player: Player, komi: isize) -> isize {
-        self.state.player_score(&self.info, player, komi)
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.is_some()
+    }
+
+    pub fn best_move(&self) -> (usize, usize) {
+        with_search!(&*self.engine(), search => search.best_move())
+    }
+
+    /**
+     * The best `count` moves from the current position, in the order they would be chosen by
+     * `best_move`. This is empty until the search has expanded the current position.
+     */
+    pub fn analysis(&self, count: usize) -> Vec<MoveAnalysis> {
+        with_search!(&*self.engine(), search => search.analysis(count))
+    }
+
+    /**
+     * Ownership and criticality of each point in the playouts run from the current position,
+     * omitting points which weren't played in any playout. This is empty until the search has
+     * been run from the current position.
+     */
+    pub fn ownership(&self) -> Vec<PointOwnership> {
+        with_search!(&*self.engine(), search => search.ownership())
+    }
+
+    /// Print the ownership and criticality of each point
+    pub fn print_ownership(&self) {
+        print!("{}", self.format_ownership());
+    }
+
+    /**
+     * The maps printed by `print_ownership`. Ownership is shown from `O` for points always
+     * occupied by the second player to `X` for points always occupied by the first, and
+     * criticality as a digit from 0 to 9.
+     */
+    pub fn format_ownership(&self) -> String {
+        with_search!(&*self.engine(), search => search.format_ownership())
+    }
+
+    /// The state of the search from the current position, or `None` if it has barely started
+    pub fn progress(&self) -> Option<SearchProgress> {
+        with_search!(&*self.engine(), search => search.progress())
+    }
+
+    /**
+     * The winner with perfect play from the current position, if the search has proven it. Once
+     * the result is proven, `best_move` returns a winning move for the winner if it is their turn.
+     */
+    pub fn proven_winner(&self) -> Option<Player> {
+        with_search!(&*self.engine(), search => search.proven_winner())
     }
 
-    pub fn finished(&self, komi: isize) -> bool {
-        self.winner(komi).is_some()
+    /// The size of the search tree and the memory it uses
+    pub fn table_usage(&self) -> TableUsage {
+        with_search!(&*self.engine(), search => search.table_usage())
+    }
+
+    /// The number of points on the board which haven't been played yet
+    pub fn empty_cells(&self) -> usize {
+        with_search!(&*self.engine(), search => search.empty_cells())
+    }
+
+    /// Play a move for the current player, leaving the game unchanged if the move is illegal
+    pub fn add_move(&mut self, x: usize, y: usize) -> Result<(), MoveError> {
+        with_search!(&mut *self.engine(), search => search.add_move(x, y))
+    }
+
+    /**
+     * Take back the last move, returning its coordinates, or `None` if no moves have been played.
+     * Only the search results below the undone move are kept, since the rest of the tree below the
+     * position before it was discarded when the move was played.
+     */
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        with_search!(&mut *self.engine(), search => search.undo())
+    }
+
+    /**
+     * Replay the most recently undone move, returning its coordinates, or `None` if there is no
+     * such move. Adding any other move discards the moves which could be redone.
+     */
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        with_search!(&mut *self.engine(), search => search.redo())
+    }
+
+    /// The coordinates of the moves played so far, in order
+    pub fn history(&self) -> Vec<(usize, usize)> {
+        with_search!(&*self.engine(), search => search.history())
+    }
+
+    pub fn size(&self) -> usize {
+        self.config().size
+    }
+
+    pub fn print_board(&self) {
+        with_search!(&*self.engine(), search => search.print_board())
+    }
+
+    /// The board as printed by `print_board`
+    pub fn format_board(&self) -> String {
+        with_search!(&*self.engine(), search => search.format_board())
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        with_search!(&*self.engine(), search => search.winner())
+    }
+
+    pub fn score(&self, player: Player) -> isize {
+        with_search!(&*self.engine(), search => search.score(player))
+    }
+
+    /**
+     * The groups making up the given player's score, assuming that every unplayed point is taken
+     * by their opponent. The score is the sum of the values of the player's groups, less the sum
+     * of the values of their opponent's groups, with komi added for the second player.
+     */
+    pub fn score_breakdown(&self, player: Player) -> Vec<GroupScore> {
+        with_search!(&*self.engine(), search => search.score_breakdown(player))
+    }
+
+    /// Print the board with each of the groups making up the player's score labelled
+    pub fn print_groups(&self, player: Player) {
+        print!("{}", self.format_groups(player));
+    }
+
+    /**
+     * The board as printed by `print_groups`. The player's groups are labelled with capital
+     * letters and their opponent's with lowercase letters, followed by the contribution of each
+     * group to the score.
+     */
+    pub fn format_groups(&self, player: Player) -> String {
+        with_search!(&*self.engine(), search => search.format_groups(player))
+    }
+
+    pub fn finished(&self) -> bool {
+        self.winner().is_some()
     }
 
     pub fn player_turn(&self) -> Player {
-        self.state.player_turn()
+        with_search!(&*self.engine(), search => search.player_turn())
+    }
+
+    /// Set the number of threads used by `calculate` and pondering, which must be at least 1
+    pub fn set_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "At least one thread is required");
+        with_search!(&mut *self.engine(), search => search.set_threads(threads))
+    }
+
+    pub fn threads(&self) -> usize {
+        with_search!(&*self.engine(), search => search.threads())
+    }
+}
+
+impl Drop for StarAI {
+    fn drop(&mut self) {
+        self.stop_pondering();
     }
 }
@@ -0,0 +1,185 @@
+/*!
+ * Mining of playout patterns from played games.
+ *
+ * For every move which answers the previous move at an adjacent point, the contents of the six
+ * points around the previous move are recorded along with the answer, identifying neighbourhoods
+ * which are the same up to rotation and reflection. Each answer is given a weight estimating its
+ * strength relative to other moves, by fitting a Bradley-Terry model in which every other empty
+ * point has strength 1, and the patterns found can be written out as a `PatternLibrary` for use in
+ * playouts.
+ */
+
+use std::collections::HashMap;
+
+use info::{in_bounds, point_count, ADJACENT};
+use patterns::{Cell, Pattern, Shape};
+use player::Player;
+use record::GameRecord;
+
+/// Number of minorization-maximization iterations used to fit the weight of each pattern
+const WEIGHT_ITERATIONS: usize = 100;
+
+/**
+ * Fit the strength of an answer played `count` times in occurrences of its neighbourhood with the
+ * given numbers of empty points, using minorization-maximization. A prior of one win and one loss
+ * against a move of strength 1 keeps the strength finite for answers which were always played.
+ */
+fn fit_weight(count: u32, empty_points: &[u32]) -> f64 {
+    let mut weight = 1.;
+    for _ in 0..WEIGHT_ITERATIONS {
+        let denominator = empty_points.iter().map(|&empty| {
+            1. / (weight + empty as f64 - 1.)
+        }).sum::<f64>() + 2. / (weight + 1.);
+        weight = (count as f64 + 1.) / denominator;
+    }
+    weight
+}
+
+/// How often an answer was played in a neighbourhood
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+struct Answers {
+    count: u32,
+    /// Number of those games won by the player answering
+    wins: u32,
+}
+
+/// A pattern found by `PatternMiner`, with the statistics it was ranked by
+#[derive(Clone, PartialEq, Debug)]
+pub struct MinedPattern {
+    pub pattern: Pattern,
+    /// Number of times the neighbourhood of the pattern occurred
+    pub occurrences: u32,
+    /// Number of times the pattern's answer was played in the neighbourhood
+    pub count: u32,
+    /// Number of games won by the player answering, of those counted in `count`
+    pub wins: u32,
+}
+
+impl MinedPattern {
+    /// The fraction of occurrences of the neighbourhood in which the answer was played
+    pub fn frequency(&self) -> f64 {
+        self.count as f64 / self.occurrences as f64
+    }
+
+    pub fn winrate(&self) -> f64 {
+        self.wins as f64 / self.count as f64
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct PatternMiner {
+    /**
+     * The number of empty points at each occurrence of each neighbourhood of a move which was
+     * answered, in canonical orientation
+     */
+    neighbourhoods: HashMap<Shape, Vec<u32>>,
+    /// Answers in each neighbourhood, as the neighbourhood with the answer as its response, in
+    /// canonical orientation
+    answers: HashMap<Shape, Answers>,
+}
+
+impl PatternMiner {
+    pub fn new() -> PatternMiner {
+        PatternMiner::default()
+    }
+
+    /// Record the answers to each move of a game. Games without a result count as lost by both
+    /// players.
+    pub fn add_game(&mut self, record: &GameRecord) {
+        let size = record.config.size;
+        let points = point_count(size);
+        let mut occupied = HashMap::new();
+        for (i, &(x, y)) in record.moves.iter().enumerate() {
+            let player = if i%2 == 0 { Player::First } else { Player::Second };
+            if i > 0 {
+                let last_move = record.moves[i - 1];
+                let won = record.result.map_or(false, |result| result.winner == player);
+                let empty_points = (points - occupied.len()) as u32;
+                self.add_answer(size, &occupied, empty_points, player, last_move, (x, y), won);
+            }
+            occupied.insert((x, y), player);
+        }
+    }
+
+    fn add_answer(
+        &mut self,
+        size: usize,
+        occupied: &HashMap<(usize, usize), Player>,
+        empty_points: u32,
+        player: Player,
+        last_move: (usize, usize),
+        answer: (usize, usize),
+        won: bool,
+    ) {
+        let mut cells = Vec::with_capacity(ADJACENT.len());
+        let mut answer_index = None;
+        for (i, &(dx, dy)) in ADJACENT.iter().enumerate() {
+            let x = last_move.0 as isize + dx;
+            let y = last_move.1 as isize + dy;
+            let cell = if x < 0 || y < 0 || !in_bounds(size, x as usize, y as usize) {
+                Cell::OffBoard
+            } else {
+                let point = (x as usize, y as usize);
+                if point == answer {
+                    answer_index = Some(i);
+                }
+                match occupied.get(&point) {
+                    None => Cell::Empty,
+                    Some(&owner) if owner == player => Cell::Ours,
+                    Some(_) => Cell::Theirs,
+                }
+            };
+            cells.push(((dx, dy), cell));
+        }
+        let neighbourhood = Shape {
+            cells: cells.clone(),
+        };
+        self.neighbourhoods.entry(neighbourhood.canonical()).or_insert_with(Vec::new).push(empty_points);
+        // Answers elsewhere on the board aren't local, but still count as occurrences
+        if let Some(i) = answer_index {
+            cells[i].1 = Cell::Response;
+            let answers = self.answers.entry(Shape { cells: cells }.canonical()).or_insert_with(Answers::default);
+            answers.count += 1;
+            if won {
+                answers.wins += 1;
+            }
+        }
+    }
+
+    /**
+     * The patterns whose answer was played at least `min_count` times, with the highest weights
+     * first. Patterns are named by their rank.
+     */
+    pub fn patterns(&self, min_count: u32) -> Vec<MinedPattern> {
+        let mut mined = self.answers.iter().filter(|&(_, answers)| answers.count >= min_count).map(|(shape, answers)| {
+            let mut neighbourhood = shape.clone();
+            for cell in neighbourhood.cells.iter_mut() {
+                if cell.1 == Cell::Response {
+                    cell.1 = Cell::Empty;
+                }
+            }
+            let empty_points = self.neighbourhoods.get(&neighbourhood.canonical()).unwrap();
+            MinedPattern {
+                pattern: Pattern {
+                    name: String::new(),
+                    // Rounded since more precision isn't meaningful, and it keeps the output legible
+                    weight: (fit_weight(answers.count, empty_points) * 100.).round() / 100.,
+                    shape: shape.clone(),
+                },
+                occurrences: empty_points.len() as u32,
+                count: answers.count,
+                wins: answers.wins,
+            }
+        }).collect::<Vec<_>>();
+        // Ties are broken by shape so that the order doesn't depend on the hash map
+        mined.sort_by(|a, b| {
+            b.pattern.weight.partial_cmp(&a.pattern.weight).unwrap()
+                .then(b.count.cmp(&a.count))
+                .then(a.pattern.shape.cmp(&b.pattern.shape))
+        });
+        for (i, mined_pattern) in mined.iter_mut().enumerate() {
+            mined_pattern.pattern.name = format!("mined-{}", i + 1);
+        }
+        mined
+    }
+}
@@ -0,0 +1,487 @@
+/*!
+ * Text definitions of the local patterns used in playouts.
+ *
+ * A pattern library consists of patterns, each starting with a `Pattern` line giving its name and
+ * followed by a diagram of the cells around the opponent's last move, drawn in the same layout as
+ * the board, with each row offset by half a cell from the one above. Blank lines and lines
+ * starting with `#` are ignored. For example:
+ *
+ * ```text
+ * # Save a bridge the opponent intruded into
+ * Pattern bridge
+ *   X @
+ *    * X
+ * ```
+ *
+ * The cells of a diagram are:
+ *
+ * - `@`: the opponent's last move.
+ * - `*`: the response, which must be empty.
+ * - `X`: occupied by the player to move.
+ * - `O`: occupied by the opponent.
+ * - `.`: empty.
+ * - `_`: off the board.
+ * - `+`: on the board, with any contents.
+ * - `?`: anything, used to fill gaps in a diagram.
+ *
+ * Each diagram must contain exactly one `@` and one `*`. Patterns are applied in all twelve
+ * rotations and reflections. The `patterns` playout policy plays the first pattern applicable to
+ * the last move, so patterns should be listed most important first.
+ *
+ * A `Weight` line between the `Pattern` line and the diagram gives the strength of the response
+ * relative to a random move, defaulting to 1. The `weighted` playout policy chooses between the
+ * applicable patterns and a random move in proportion to their strengths, where a random move
+ * has the strength of all the empty points together, so that a pattern with weight `w` is played
+ * about `w` times as often as any particular other point.
+ */
+
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
+
+use getopts::{Matches, Options};
+
+/// The library used when no other is given
+const DEFAULT_PATTERNS: &'static str = include_str!("../patterns/default.txt");
+/// Weight of patterns without a `Weight` line
+pub const DEFAULT_WEIGHT: f64 = 1.;
+
+/// What a cell of a pattern must contain for the pattern to apply
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Cell {
+    /// The point to play, which must be empty
+    Response,
+    Ours,
+    Theirs,
+    Empty,
+    OffBoard,
+    OnBoard,
+}
+
+/// A pattern in one orientation, with cells given by their offset from the last move
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Shape {
+    pub cells: Vec<((isize, isize), Cell)>,
+}
+
+impl Shape {
+    /// The distinct orientations of the shape under rotation and reflection
+    pub fn orientations(&self) -> Vec<Shape> {
+        let mut orientations: Vec<Shape> = Vec::new();
+        let mut shape = self.clone();
+        for _ in 0..2 {
+            for _ in 0..6 {
+                let sorted = shape.sorted();
+                if !orientations.contains(&sorted) {
+                    orientations.push(sorted);
+                }
+                shape = shape.rotate();
+            }
+            shape = shape.reflect();
+        }
+        orientations
+    }
+
+    /// The orientation used to identify shapes which are the same up to rotation and reflection
+    pub fn canonical(&self) -> Shape {
+        self.orientations().into_iter().min().unwrap()
+    }
+
+    /// Rotate by 60 degrees, in the direction of the order of adjacent points in `BoardInfo`
+    fn rotate(&self) -> Shape {
+        Shape {
+            cells: self.cells.iter().map(|&((dx, dy), cell)| ((dx + dy, -dx), cell)).collect(),
+        }
+    }
+
+    /// Reflect in the line through the last move where the offsets along each axis are equal
+    fn reflect(&self) -> Shape {
+        Shape {
+            cells: self.cells.iter().map(|&((dx, dy), cell)| ((dy, dx), cell)).collect(),
+        }
+    }
+
+    /// The same shape with its cells in a canonical order, for comparing orientations
+    fn sorted(&self) -> Shape {
+        let mut cells = self.cells.clone();
+        cells.sort_by_key(|&(offset, _)| offset);
+        Shape {
+            cells: cells,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    pub name: String,
+    /// Strength of the response relative to a random move
+    pub weight: f64,
+    pub shape: Shape,
+}
+
+impl Pattern {
+    /// The distinct orientations of the pattern under rotation and reflection
+    pub fn orientations(&self) -> Vec<Shape> {
+        self.shape.orientations()
+    }
+}
+
+impl fmt::Display for Pattern {
+    /// Writes the `Pattern` line and diagram, with `?` for unconstrained cells inside the diagram
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Pattern {}", self.name)?;
+        if self.weight != DEFAULT_WEIGHT {
+            writeln!(f, "Weight {}", self.weight)?;
+        }
+        let offsets = self.shape.cells.iter().map(|&(offset, _)| offset).chain(Some((0, 0)));
+        let (mut min_dx, mut max_dx, mut min_dy, mut max_dy) = (0, 0, 0, 0);
+        for (dx, dy) in offsets {
+            min_dx = cmp::min(min_dx, dx);
+            max_dx = cmp::max(max_dx, dx);
+            min_dy = cmp::min(min_dy, dy);
+            max_dy = cmp::max(max_dy, dy);
+        }
+        for dy in min_dy..max_dy + 1 {
+            let row = (min_dx..max_dx + 1).map(|dx| {
+                if (dx, dy) == (0, 0) {
+                    return '@';
+                }
+                match self.shape.cells.iter().find(|&&(offset, _)| offset == (dx, dy)) {
+                    None => '?',
+                    Some(&(_, cell)) => cell_char(cell),
+                }
+            }).map(|c| c.to_string()).collect::<Vec<_>>();
+            let padding = iter::repeat(" ").take((dy - min_dy) as usize).collect::<String>();
+            writeln!(f, "  {}{}", padding, row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reasons a pattern library could not be read
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    /// A line of the library (numbered from 1) could not be parsed
+    Parse(usize, String),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatternError::Io(ref e) => write!(f, "Failed to read patterns: {}", e),
+            PatternError::Parse(line, ref message) => write!(f, "Line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for PatternError {}
+
+impl From<io::Error> for PatternError {
+    fn from(e: io::Error) -> PatternError {
+        PatternError::Io(e)
+    }
+}
+
+fn cell_char(cell: Cell) -> char {
+    match cell {
+        Cell::Response => '*',
+        Cell::Ours => 'X',
+        Cell::Theirs => 'O',
+        Cell::Empty => '.',
+        Cell::OffBoard => '_',
+        Cell::OnBoard => '+',
+    }
+}
+
+fn parse_cell(c: char) -> Option<Option<Cell>> {
+    match c {
+        '*' => Some(Some(Cell::Response)),
+        'X' => Some(Some(Cell::Ours)),
+        'O' => Some(Some(Cell::Theirs)),
+        '.' => Some(Some(Cell::Empty)),
+        '_' => Some(Some(Cell::OffBoard)),
+        '+' => Some(Some(Cell::OnBoard)),
+        '?' => Some(None),
+        _ => None,
+    }
+}
+
+/// A pattern whose diagram is still being read
+struct PatternBuilder {
+    name: String,
+    /// Line number of the `Pattern` line
+    line: usize,
+    weight: f64,
+    /// Each constrained cell with the row and column of the diagram it appears at
+    cells: Vec<((usize, usize), Cell)>,
+    last_move: Option<(usize, usize)>,
+    rows: usize,
+}
+
+impl PatternBuilder {
+    fn add_row(&mut self, line_number: usize, row: &str) -> Result<(), PatternError> {
+        for (column, c) in row.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let position = (self.rows, column);
+            if c == '@' {
+                if self.last_move.is_some() {
+                    return Err(PatternError::Parse(line_number, "More than one last move".to_string()));
+                }
+                self.last_move = Some(position);
+                continue;
+            }
+            match parse_cell(c) {
+                None => return Err(PatternError::Parse(line_number, format!("Invalid cell '{}'", c))),
+                Some(None) => {},
+                Some(Some(cell)) => self.cells.push((position, cell)),
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn build(self) -> Result<Pattern, PatternError> {
+        let (last_row, last_column) = match self.last_move {
+            None => return Err(PatternError::Parse(self.line, format!("Pattern {} has no last move", self.name))),
+            Some(position) => position,
+        };
+        let responses = self.cells.iter().filter(|&&(_, cell)| cell == Cell::Response).count();
+        if responses != 1 {
+            return Err(PatternError::Parse(self.line, format!("Pattern {} must have exactly one response", self.name)));
+        }
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for &((row, column), cell) in self.cells.iter() {
+            let dy = row as isize - last_row as isize;
+            // Each row is drawn offset by half a cell, i.e. one column, from the one above
+            let columns = column as isize - last_column as isize - dy;
+            if columns%2 != 0 {
+                return Err(PatternError::Parse(self.line, format!("Pattern {} has cells between points", self.name)));
+            }
+            cells.push(((columns / 2, dy), cell));
+        }
+        Ok(Pattern {
+            name: self.name,
+            weight: self.weight,
+            shape: Shape {
+                cells: cells,
+            },
+        })
+    }
+}
+
+/// Patterns in order of priority
+#[derive(Clone, PartialEq, Debug)]
+pub struct PatternLibrary {
+    pub patterns: Vec<Pattern>,
+}
+
+impl Default for PatternLibrary {
+    fn default() -> PatternLibrary {
+        DEFAULT_PATTERNS.parse().unwrap_or_else(|e| {
+            panic!("Invalid default patterns: {}", e);
+        })
+    }
+}
+
+impl PatternLibrary {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PatternLibrary, PatternError> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        contents.parse()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        File::create(path)?.write_all(self.to_string().as_bytes())
+    }
+
+    /// Add a command line option for loading a library
+    pub fn add_options(opts: &mut Options) {
+        opts.optopt("", "patterns", "load the patterns used in playouts from FILE", "FILE");
+    }
+
+    /// Load the library given by the option added by `add_options`, or the default one
+    pub fn from_matches(matches: &Matches) -> Result<PatternLibrary, String> {
+        match matches.opt_str("patterns") {
+            None => Ok(PatternLibrary::default()),
+            Some(path) => PatternLibrary::load(&path).map_err(|e| format!("{}: {}", path, e)),
+        }
+    }
+}
+
+impl FromStr for PatternLibrary {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<PatternLibrary, PatternError> {
+        let mut patterns = Vec::new();
+        let mut current: Option<PatternBuilder> = None;
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if trimmed.starts_with("Pattern") {
+                if let Some(builder) = current.take() {
+                    patterns.push(builder.build()?);
+                }
+                current = Some(PatternBuilder {
+                    name: trimmed["Pattern".len()..].trim().to_string(),
+                    line: line_number,
+                    cells: Vec::new(),
+                    weight: DEFAULT_WEIGHT,
+                    last_move: None,
+                    rows: 0,
+                });
+                continue;
+            }
+            if trimmed.starts_with("Weight") {
+                match current {
+                    Some(ref mut builder) if builder.rows == 0 => {
+                        let value = trimmed["Weight".len()..].trim();
+                        builder.weight = match value.parse::<f64>() {
+                            Ok(weight) if weight > 0. && weight.is_finite() => weight,
+                            _ => return Err(PatternError::Parse(line_number, format!("Invalid weight '{}'", value))),
+                        };
+                    },
+                    _ => return Err(PatternError::Parse(line_number, "Weight must follow a Pattern line".to_string())),
+                }
+                continue;
+            }
+            match current {
+                None => return Err(PatternError::Parse(line_number, "Expected a Pattern line".to_string())),
+                Some(ref mut builder) => builder.add_row(line_number, line.trim_end())?,
+            }
+        }
+        if let Some(builder) = current {
+            patterns.push(builder.build()?);
+        }
+        Ok(PatternLibrary {
+            patterns: patterns,
+        })
+    }
+}
+
+impl fmt::Display for PatternLibrary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", pattern)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error_line(s: &str) -> usize {
+        match s.parse::<PatternLibrary>() {
+            Err(PatternError::Parse(line, _)) => line,
+            result => panic!("Expected a parse error, got {:?}", result),
+        }
+    }
+
+    fn shape(cells: &[((isize, isize), Cell)]) -> Shape {
+        Shape {
+            cells: cells.to_vec(),
+        }
+    }
+
+    #[test]
+    fn parses_offsets() {
+        let library = "Pattern bridge\n  X @\n   * X\n".parse::<PatternLibrary>().unwrap();
+        assert_eq!(library.patterns.len(), 1);
+        let pattern = &library.patterns[0];
+        assert_eq!(pattern.name, "bridge");
+        assert_eq!(pattern.weight, DEFAULT_WEIGHT);
+        assert_eq!(pattern.shape.sorted(), shape(&[
+            ((-1, 0), Cell::Ours),
+            ((-1, 1), Cell::Response),
+            ((0, 1), Cell::Ours),
+        ]).sorted());
+    }
+
+    #[test]
+    fn default_library_round_trips() {
+        let library = PatternLibrary::default();
+        assert!(!library.patterns.is_empty());
+        let printed = library.to_string();
+        assert_eq!(printed.parse::<PatternLibrary>().unwrap(), library);
+        assert_eq!(printed.parse::<PatternLibrary>().unwrap().to_string(), printed);
+    }
+
+    #[test]
+    fn round_trips_gaps_and_weights() {
+        let text = "Pattern gap\nWeight 2.5\n  ? O ?\n   @ ? _\n    + . *\n";
+        let library = text.parse::<PatternLibrary>().unwrap();
+        assert_eq!(library.patterns[0].weight, 2.5);
+        assert_eq!(library.to_string().parse::<PatternLibrary>().unwrap(), library);
+    }
+
+    #[test]
+    fn reports_error_lines() {
+        assert_eq!(parse_error_line("  X @ *"), 1);
+        assert_eq!(parse_error_line("Pattern a\n  X @ *\n  @"), 3);
+        assert_eq!(parse_error_line("Pattern a\n  X @ Z"), 2);
+        assert_eq!(parse_error_line("# comment\n\nPattern a\n  X *"), 3);
+        assert_eq!(parse_error_line("Pattern a\n  X @ *\n\nPattern b\n  * @ *"), 4);
+        assert_eq!(parse_error_line("Pattern a\n  X @  *"), 1);
+        assert_eq!(parse_error_line("Pattern a\nWeight 0\n  X @ *"), 2);
+        assert_eq!(parse_error_line("Pattern a\n  X @ *\nWeight 2"), 3);
+        assert_eq!(parse_error_line("Weight 2\nPattern a\n  X @ *"), 1);
+    }
+
+    #[test]
+    fn asymmetric_shape_has_twelve_orientations() {
+        let asymmetric = shape(&[((1, 0), Cell::Ours), ((2, 0), Cell::Theirs), ((0, 1), Cell::Response)]);
+        let orientations = asymmetric.orientations();
+        assert_eq!(orientations.len(), 12);
+        for orientation in orientations.iter() {
+            assert_eq!(orientation.canonical(), asymmetric.canonical());
+        }
+    }
+
+    #[test]
+    fn symmetric_shapes_have_fewer_orientations() {
+        // Symmetric under reflection in the line through the last move and the response
+        let bridge = shape(&[((-1, 0), Cell::Ours), ((-1, 1), Cell::Response), ((0, 1), Cell::Ours)]);
+        assert_eq!(bridge.orientations().len(), 6);
+        assert_eq!(shape(&[((1, 0), Cell::Response)]).orientations().len(), 6);
+        let ring = shape(&[
+            ((-1, 0), Cell::Empty),
+            ((-1, 1), Cell::Empty),
+            ((0, 1), Cell::Empty),
+            ((1, 0), Cell::Empty),
+            ((1, -1), Cell::Empty),
+            ((0, -1), Cell::Empty),
+        ]);
+        assert_eq!(ring.orientations().len(), 1);
+    }
+
+    #[test]
+    fn rotations_and_reflections_are_cyclic() {
+        let asymmetric = shape(&[((1, 0), Cell::Ours), ((2, -1), Cell::Theirs), ((0, 2), Cell::Response)]);
+        let mut rotated = asymmetric.clone();
+        for _ in 0..6 {
+            rotated = rotated.rotate();
+        }
+        assert_eq!(rotated, asymmetric);
+        assert_eq!(asymmetric.reflect().reflect(), asymmetric);
+        // Rotations stay on the hexagonal grid, keeping each cell at the same distance
+        let distance = |(dx, dy): (isize, isize)| cmp::max(cmp::max(dx.abs(), dy.abs()), (dx + dy).abs());
+        for &(offset, _) in asymmetric.rotate().cells.iter().chain(asymmetric.reflect().cells.iter()) {
+            assert!([1, 2].contains(&distance(offset)));
+        }
+    }
+}
@@ -4,4 +4,13 @@ pub enum Player {
     Second,
 }
 
+impl Player {
+    pub fn opponent(&self) -> Player {
+        match *self {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        }
+    }
+}
+
 pub const PLAYERS: [Player; 2] = [Player::First, Player::Second];
@@ -0,0 +1,114 @@
+/*!
+ * Policies choosing the moves played in playouts.
+ *
+ * A playout repeatedly asks its policy for a move, and plays the next point of a random
+ * permutation of the empty points whenever the policy has no preference, so policies only need to
+ * recognise the situations in which they know better than a uniformly random move.
+ */
+
+use rand::{Rng, XorShiftRng};
+
+use board::BoardState;
+use config::PlayoutPolicyKind;
+use info::BoardInfo;
+
+/// Chooses moves for playouts, shared between the threads running them
+pub trait PlayoutPolicy<const W: usize>: Sync {
+    /**
+     * Choose an empty point for the player to move in `state` to play, where `last_move` is the
+     * opponent's previous move in the playout, if there was one. Returns `None` to play a
+     * uniformly random empty point instead.
+     */
+    fn choose(&self, info: &BoardInfo<W>, state: &BoardState<W>, last_move: Option<usize>, rng: &mut XorShiftRng) -> Option<usize>;
+}
+
+/// Plays uniformly random moves
+#[derive(Copy, Clone, Debug)]
+pub struct UniformPolicy;
+
+impl<const W: usize> PlayoutPolicy<W> for UniformPolicy {
+    fn choose(&self, _: &BoardInfo<W>, _: &BoardState<W>, _: Option<usize>, _: &mut XorShiftRng) -> Option<usize> {
+        None
+    }
+}
+
+/// Responds to the opponent's last move with the first applicable pattern from `BoardInfo`, e.g.
+/// to save a bridge it intruded into
+#[derive(Copy, Clone, Debug)]
+pub struct PatternPolicy;
+
+impl<const W: usize> PlayoutPolicy<W> for PatternPolicy {
+    fn choose(&self, info: &BoardInfo<W>, state: &BoardState<W>, last_move: Option<usize>, _: &mut XorShiftRng) -> Option<usize> {
+        let last_pos = last_move?;
+        let (last_player, current_player) = if state.moves%2 == 0 {
+            (state.second_player, state.first_player)
+        } else {
+            (state.first_player, state.second_player)
+        };
+        info.patterns[last_pos].iter().find_map(|pattern| pattern.check(&last_player, &current_player))
+    }
+}
+
+/**
+ * Chooses between the patterns applicable to the opponent's last move and a random move in
+ * proportion to the weights of the patterns, where a random move has a weight of one for each
+ * empty point
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct WeightedPatternPolicy;
+
+impl<const W: usize> PlayoutPolicy<W> for WeightedPatternPolicy {
+    fn choose(&self, info: &BoardInfo<W>, state: &BoardState<W>, last_move: Option<usize>, rng: &mut XorShiftRng) -> Option<usize> {
+        let last_pos = last_move?;
+        let (last_player, current_player) = if state.moves%2 == 0 {
+            (state.second_player, state.first_player)
+        } else {
+            (state.first_player, state.second_player)
+        };
+        let patterns = &info.patterns[last_pos];
+        let total = patterns.iter().filter(|pattern| pattern.check(&last_player, &current_player).is_some())
+            .map(|pattern| pattern.weight).sum::<f64>();
+        if total == 0. {
+            return None;
+        }
+        // Patterns are checked again rather than collected, to avoid allocating for every move
+        let mut choice = rng.gen::<f64>() * (total + (info.count - state.moves) as f64);
+        for pattern in patterns.iter() {
+            if let Some(pos) = pattern.check(&last_player, &current_player) {
+                if choice < pattern.weight {
+                    return Some(pos);
+                }
+                choice -= pattern.weight;
+            }
+        }
+        None
+    }
+}
+
+/// The policy selected by the search configuration
+#[derive(Copy, Clone, Debug)]
+pub enum ConfiguredPolicy {
+    Uniform(UniformPolicy),
+    Patterns(PatternPolicy),
+    Weighted(WeightedPatternPolicy),
+}
+
+impl ConfiguredPolicy {
+    pub fn new(kind: PlayoutPolicyKind) -> ConfiguredPolicy {
+        match kind {
+            PlayoutPolicyKind::Uniform => ConfiguredPolicy::Uniform(UniformPolicy),
+            PlayoutPolicyKind::Patterns => ConfiguredPolicy::Patterns(PatternPolicy),
+            PlayoutPolicyKind::Weighted => ConfiguredPolicy::Weighted(WeightedPatternPolicy),
+        }
+    }
+}
+
+impl<const W: usize> PlayoutPolicy<W> for ConfiguredPolicy {
+    fn choose(&self, info: &BoardInfo<W>, state: &BoardState<W>, last_move: Option<usize>, rng: &mut XorShiftRng) -> Option<usize> {
+        match *self {
+            ConfiguredPolicy::Uniform(ref policy) => policy.choose(info, state, last_move, rng),
+            ConfiguredPolicy::Patterns(ref policy) => policy.choose(info, state, last_move, rng),
+            ConfiguredPolicy::Weighted(ref policy) => policy.choose(info, state, last_move, rng),
+        }
+    }
+}
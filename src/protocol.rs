@@ -0,0 +1,35 @@
+/*!
+ * Players and points as written in the GTP-style protocol spoken by the player binary, shared
+ * with the compare binary which drives it.
+ */
+
+use player::Player;
+
+/// Parse a player, written `first` or `second`, or as `b`/`black` or `w`/`white`
+pub fn parse_player(value: &str) -> Result<Player, String> {
+    match value.to_lowercase().as_str() {
+        "first" | "b" | "black" => Ok(Player::First),
+        "second" | "w" | "white" => Ok(Player::Second),
+        _ => Err(format!("invalid player '{}'", value)),
+    }
+}
+
+pub fn format_player(player: Player) -> &'static str {
+    match player {
+        Player::First => "first",
+        Player::Second => "second",
+    }
+}
+
+/// Parse a point written `x,y`, using the zero based coordinates used by `StarAI`
+pub fn parse_point(value: &str) -> Result<(usize, usize), String> {
+    let coords = value.split(',').map(|c| c.parse::<usize>()).collect::<Vec<_>>();
+    match coords.as_slice() {
+        [Ok(x), Ok(y)] => Ok((*x, *y)),
+        _ => Err(format!("invalid point '{}'", value)),
+    }
+}
+
+pub fn format_point((x, y): (usize, usize)) -> String {
+    format!("{},{}", x, y)
+}
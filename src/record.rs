@@ -0,0 +1,295 @@
+/*!
+ * Text records of complete or partial games.
+ *
+ * A record consists of one entry per line, each a keyword followed by its value. Blank lines and
+ * lines starting with `#` are ignored. For example:
+ *
+ * ```text
+ * # Game played with the compare binary
+ * Size 5
+ * Komi 1
+ * First A (master)
+ * Second B (HEAD)
+ * Move 4 4
+ * Move 3 5
+ * Result First 3
+ * ```
+ *
+ * - `Size` is the length of a side of the board, and is required.
+ * - `Komi` is the komi given to the second player, and defaults to 0.
+ * - `First` and `Second` are the names of the players, which may contain spaces.
+ * - Each `Move` line gives the zero based `x y` coordinates of a move, in the same coordinate
+ *   system used by `StarAI`, in the order they were played starting with the first player.
+ * - `Result` gives the winner followed by their final score, and is omitted for unfinished games.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use config::{GameConfig, ConfigError};
+use player::Player;
+use {StarAI, MoveError};
+
+/// The outcome of a finished game
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GameResult {
+    pub winner: Player,
+    /// The final score of the winner
+    pub score: isize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GameRecord {
+    pub config: GameConfig,
+    /// Name of the first player
+    pub first: String,
+    /// Name of the second player
+    pub second: String,
+    /// Coordinates of each move, in order
+    pub moves: Vec<(usize, usize)>,
+    pub result: Option<GameResult>,
+}
+
+/// Reasons a record could not be read or replayed
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    /// A line of the record (numbered from 1) could not be parsed
+    Parse(usize, String),
+    Config(ConfigError),
+    /// A move in the record (numbered from 0) is illegal
+    IllegalMove(usize, MoveError),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordError::Io(ref e) => write!(f, "Failed to access record: {}", e),
+            RecordError::Parse(line, ref message) => write!(f, "Line {}: {}", line, message),
+            RecordError::Config(ref e) => write!(f, "{}", e),
+            RecordError::IllegalMove(index, ref e) => write!(f, "Move {}: {}", index, e),
+        }
+    }
+}
+
+impl Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(e: io::Error) -> RecordError {
+        RecordError::Io(e)
+    }
+}
+
+fn parse_value<F: FromStr>(line: usize, value: &str) -> Result<F, RecordError> {
+    value.parse::<F>().map_err(|_| {
+        RecordError::Parse(line, format!("Invalid value '{}'", value))
+    })
+}
+
+fn parse_player(line: usize, value: &str) -> Result<Player, RecordError> {
+    match value {
+        "First" => Ok(Player::First),
+        "Second" => Ok(Player::Second),
+        _ => Err(RecordError::Parse(line, format!("Invalid player '{}'", value))),
+    }
+}
+
+impl GameRecord {
+    pub fn new(config: GameConfig, first: &str, second: &str) -> GameRecord {
+        GameRecord {
+            config: config,
+            first: first.to_string(),
+            second: second.to_string(),
+            moves: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Create a record of the game played so far by `ai`
+    pub fn from_ai(ai: &StarAI, first: &str, second: &str) -> GameRecord {
+        let mut record = GameRecord::new(ai.config(), first, second);
+        record.moves = ai.history();
+        record.result = ai.winner().map(|winner| {
+            GameResult {
+                winner: winner,
+                score: ai.score(winner),
+            }
+        });
+        record
+    }
+
+    /// Create an AI with all the moves of this record played
+    pub fn replay(&self) -> Result<StarAI, RecordError> {
+        self.config.validate().map_err(RecordError::Config)?;
+        let mut ai = StarAI::new(self.config);
+        for (i, &(x, y)) in self.moves.iter().enumerate() {
+            ai.add_move(x, y).map_err(|e| RecordError::IllegalMove(i, e))?;
+        }
+        Ok(ai)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GameRecord, RecordError> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        contents.parse()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        File::create(path)?.write_all(self.to_string().as_bytes())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<GameRecord, RecordError> {
+        let mut size = None;
+        let mut record = GameRecord::new(GameConfig::new(0, 0), "", "");
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, value) = match line.find(' ') {
+                None => (line, ""),
+                Some(split) => (&line[..split], line[split + 1..].trim()),
+            };
+            let values = value.split_whitespace().collect::<Vec<&str>>();
+            match keyword {
+                "Size" => size = Some(parse_value(line_number, value)?),
+                "Komi" => record.config.komi = parse_value(line_number, value)?,
+                "First" => record.first = value.to_string(),
+                "Second" => record.second = value.to_string(),
+                "Move" => {
+                    if values.len() != 2 {
+                        return Err(RecordError::Parse(line_number, "Expected two coordinates".to_string()));
+                    }
+                    let x = parse_value(line_number, values[0])?;
+                    let y = parse_value(line_number, values[1])?;
+                    record.moves.push((x, y));
+                },
+                "Result" => {
+                    if values.len() != 2 {
+                        return Err(RecordError::Parse(line_number, "Expected a winner and a score".to_string()));
+                    }
+                    record.result = Some(GameResult {
+                        winner: parse_player(line_number, values[0])?,
+                        score: parse_value(line_number, values[1])?,
+                    });
+                },
+                _ => {
+                    return Err(RecordError::Parse(line_number, format!("Unknown keyword '{}'", keyword)));
+                },
+            }
+        }
+        match size {
+            None => Err(RecordError::Parse(s.lines().count(), "Missing board size".to_string())),
+            Some(size) => {
+                record.config.size = size;
+                Ok(record)
+            },
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Size {}", self.config.size)?;
+        writeln!(f, "Komi {}", self.config.komi)?;
+        writeln!(f, "First {}", self.first)?;
+        writeln!(f, "Second {}", self.second)?;
+        for &(x, y) in self.moves.iter() {
+            writeln!(f, "Move {} {}", x, y)?;
+        }
+        if let Some(result) = self.result {
+            writeln!(f, "Result {:?} {}", result.winner, result.score)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &'static str = "\
+# Game played with the compare binary
+Size 5
+Komi 1
+First A (master)
+Second B (HEAD)
+
+Move 4 4
+Move 3 5
+Result First 3
+";
+
+    fn parse_error_line(s: &str) -> usize {
+        match s.parse::<GameRecord>() {
+            Err(RecordError::Parse(line, _)) => line,
+            result => panic!("Expected a parse error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parses_example() {
+        let record = EXAMPLE.parse::<GameRecord>().unwrap();
+        assert_eq!(record.config, GameConfig::new(5, 1));
+        assert_eq!(record.first, "A (master)");
+        assert_eq!(record.second, "B (HEAD)");
+        assert_eq!(record.moves, vec![(4, 4), (3, 5)]);
+        assert_eq!(record.result, Some(GameResult { winner: Player::First, score: 3 }));
+    }
+
+    #[test]
+    fn round_trips() {
+        let record = EXAMPLE.parse::<GameRecord>().unwrap();
+        let printed = record.to_string();
+        assert_eq!(printed.parse::<GameRecord>().unwrap(), record);
+        assert_eq!(printed.parse::<GameRecord>().unwrap().to_string(), printed);
+
+        let mut unfinished = GameRecord::new(GameConfig::new(7, -2), "", "two words");
+        unfinished.moves = vec![(1, 2), (3, 4)];
+        assert_eq!(unfinished.to_string().parse::<GameRecord>().unwrap(), unfinished);
+    }
+
+    #[test]
+    fn komi_defaults_to_zero() {
+        assert_eq!("Size 4".parse::<GameRecord>().unwrap().config, GameConfig::new(4, 0));
+    }
+
+    #[test]
+    fn reports_error_lines() {
+        assert_eq!(parse_error_line("Size 5\nKomi x"), 2);
+        assert_eq!(parse_error_line("Size 5\n\n# comment\nMove 1"), 4);
+        assert_eq!(parse_error_line("Size 5\nMove 1 2 3"), 2);
+        assert_eq!(parse_error_line("Size 5\nResult Nobody 3"), 2);
+        assert_eq!(parse_error_line("Size 5\nResult First"), 2);
+        assert_eq!(parse_error_line("Size 5\nPass"), 2);
+        assert_eq!(parse_error_line("Komi 1\nMove 1 2"), 2);
+    }
+
+    #[test]
+    fn replays_game() {
+        let mut ai = StarAI::new(GameConfig::new(5, 1));
+        for &(x, y) in [(4, 4), (3, 5), (2, 6)].iter() {
+            ai.add_move(x, y).unwrap();
+        }
+        let record = GameRecord::from_ai(&ai, "first", "second").to_string().parse::<GameRecord>().unwrap();
+        assert_eq!(record.replay().unwrap().history(), ai.history());
+    }
+
+    #[test]
+    fn rejects_illegal_moves_on_replay() {
+        let record = "Size 5\nMove 4 4\nMove 4 4".parse::<GameRecord>().unwrap();
+        match record.replay() {
+            Err(RecordError::IllegalMove(1, MoveError::Occupied)) => {},
+            result => panic!("Expected an illegal move, got {:?}", result.map(|ai| ai.history())),
+        }
+    }
+}
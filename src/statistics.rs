@@ -13,7 +13,7 @@ pub struct Stats {
 }
 
 impl Stats {
-    pub fn new(info: &BoardInfo) -> Stats {
+    pub fn new<const W: usize>(info: &BoardInfo<W>) -> Stats {
         let mut points = Vec::with_capacity(info.count as usize);
         for _ in 0..info.count {
             points.push(PointStats::new());
@@ -29,7 +29,7 @@ impl Stats {
      * Return win statistics as if the first player won `count` times. This is used for nodes where
      * the entire board has already been filled, so the winner is always determined.
      */
-    pub fn single(info: &BoardInfo, winner: Player, count: u32) -> Stats {
+    pub fn single<const W: usize>(info: &BoardInfo<W>, winner: Player, count: u32) -> Stats {
         let mut points = Vec::with_capacity(info.count as usize);
         for _ in 0..info.count {
             points.push(PointStats::new());
@@ -45,6 +45,20 @@ impl Stats {
         }
     }
 
+    /**
+     * Add the games recorded in `other`, which must be for the same board, to these statistics
+     */
+    pub fn merge(&mut self, other: &Stats) {
+        self.count += other.count;
+        self.first_player_wins += other.first_player_wins;
+        for (point, other_point) in self.point_stats.iter_mut().zip(other.point_stats.iter()) {
+            point.p1 += other_point.p1;
+            point.p1_wins += other_point.p1_wins;
+            point.p2 += other_point.p2;
+            point.p2_wins += other_point.p2_wins;
+        }
+    }
+
     /**
      * Record a game along with the winner
      */
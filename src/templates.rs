@@ -0,0 +1,180 @@
+/*!
+ * Edge templates: shapes in which a stone is connected to the edge of the board however the
+ * opponent plays, as long as its owner answers each intrusion into the template.
+ *
+ * Templates are given by their carrier, the empty points the connection depends on. Each placement
+ * of a carrier on the board is checked by searching all the ways the points of the carrier could be
+ * filled, so templates are found wherever they hold, including around corners where the edge
+ * bends, and the answer to each intrusion comes from the same search.
+ */
+
+use fnv::FnvHashMap;
+
+use boardvec::BoardVec;
+use info::{BoardInfo, Pattern};
+use patterns::{Cell, Shape};
+
+/**
+ * Offsets of the carrier of each template from its stone, in one orientation, with the edge
+ * towards smaller `y`: the second row template, with the two points between the stone and the
+ * edge, and the third row ziggurat.
+ */
+const CARRIERS: [&'static [(isize, isize)]; 2] = [
+    &[(0, -1), (1, -1)],
+    &[(1, 0), (0, -1), (1, -1), (2, -1), (0, -2), (1, -2), (2, -2), (3, -2)],
+];
+
+/// Weight of the patterns answering intrusions into templates, for the weighted playout policy
+const TEMPLATE_WEIGHT: f64 = 100.;
+
+/// A template placed on the board
+#[derive(Clone, Debug)]
+pub struct Template<const W: usize> {
+    /// The stone connected to the edge
+    pub stone: usize,
+    pub carrier: BoardVec<W>,
+    /// Each intrusion into the carrier which would break the connection if left unanswered, with
+    /// the answer which restores it
+    pub answers: Vec<(usize, usize)>,
+}
+
+/**
+ * Whether the stone at `stone` is connected to the edge through its own stones on the points of
+ * `cells` given by the bits of `allowed`
+ */
+fn touches_edge<const W: usize>(info: &BoardInfo<W>, stone: usize, cells: &[usize], allowed: u32) -> bool {
+    let mut visited = 0u32;
+    let mut stack = vec![stone];
+    while let Some(point) = stack.pop() {
+        for &other_point in info.adjacencies[point].iter() {
+            if let Some(index) = cells.iter().position(|&cell| cell == other_point) {
+                let bit = 1 << index;
+                if allowed & bit != 0 && visited & bit == 0 {
+                    if info.on_boundary(other_point) {
+                        return true;
+                    }
+                    visited |= bit;
+                    stack.push(other_point);
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Results of `connects` for one carrier, keyed by its arguments
+type Memo = FnvHashMap<(u32, u32, bool), bool>;
+
+/**
+ * Whether the owner of the stone can connect it to the edge within the carrier `cells`, where the
+ * bits of `ours` and `theirs` give the points of the carrier already taken by each player
+ */
+fn connects<const W: usize>(
+    info: &BoardInfo<W>,
+    stone: usize,
+    cells: &[usize],
+    memo: &mut Memo,
+    ours: u32,
+    theirs: u32,
+    their_turn: bool,
+) -> bool {
+    if let Some(&result) = memo.get(&(ours, theirs, their_turn)) {
+        return result;
+    }
+    let all = (1 << cells.len()) - 1;
+    let result = if touches_edge(info, stone, cells, ours) {
+        true
+    } else if !touches_edge(info, stone, cells, all & !theirs) {
+        false
+    } else {
+        let mut free = (0..cells.len()).map(|i| 1 << i).filter(|bit| (ours | theirs) & bit == 0);
+        if their_turn {
+            free.all(|bit| connects(info, stone, cells, memo, ours, theirs | bit, false))
+        } else {
+            free.any(|bit| connects(info, stone, cells, memo, ours | bit, theirs, true))
+        }
+    };
+    memo.insert((ours, theirs, their_turn), result);
+    result
+}
+
+/// Check the template with carrier `cells` around `stone`, returning it if it holds
+fn place<const W: usize>(info: &BoardInfo<W>, stone: usize, cells: &[usize]) -> Option<Template<W>> {
+    let mut memo = Memo::default();
+    if !connects(info, stone, cells, &mut memo, 0, 0, true) {
+        return None;
+    }
+    let mut carrier = BoardVec::new();
+    let mut answers = Vec::new();
+    for (i, &intrusion) in cells.iter().enumerate() {
+        carrier.set(intrusion);
+        let theirs = 1 << i;
+        if connects(info, stone, cells, &mut memo, 0, theirs, true) {
+            continue;
+        }
+        let answer = (0..cells.len()).find(|&j| j != i && connects(info, stone, cells, &mut memo, 1 << j, theirs, true));
+        answers.push((intrusion, cells[answer.unwrap()]));
+    }
+    Some(Template {
+        stone: stone,
+        carrier: carrier,
+        answers: answers,
+    })
+}
+
+/// Find every placement of a template on the board, in every orientation
+pub fn find_templates<const W: usize>(info: &BoardInfo<W>) -> Vec<Template<W>> {
+    let mut templates = Vec::new();
+    for offsets in CARRIERS.iter() {
+        let shape = Shape {
+            cells: offsets.iter().map(|&offset| (offset, Cell::Empty)).collect(),
+        };
+        let orientations = shape.orientations();
+        for stone in 0..info.count {
+            if info.on_boundary(stone) {
+                continue;
+            }
+            let (x, y) = info.coords[stone];
+            for orientation in orientations.iter() {
+                let cells = orientation.cells.iter().map(|&((dx, dy), _)| {
+                    let (cell_x, cell_y) = (x as isize + dx, y as isize + dy);
+                    if cell_x < 0 || cell_y < 0 {
+                        None
+                    } else {
+                        info.reverse_coords.get(&(cell_x as usize, cell_y as usize)).cloned()
+                    }
+                }).collect::<Option<Vec<usize>>>();
+                if let Some(template) = cells.and_then(|cells| place(info, stone, &cells)) {
+                    templates.push(template);
+                }
+            }
+        }
+    }
+    templates
+}
+
+/**
+ * Patterns answering each intrusion into the templates, indexed by the point of the intrusion. The
+ * patterns apply while the rest of the carrier is empty.
+ */
+pub fn template_patterns<const W: usize>(info: &BoardInfo<W>, templates: &[Template<W>]) -> Vec<Vec<Pattern<W>>> {
+    let mut patterns = vec![Vec::new(); info.count];
+    for template in templates.iter() {
+        let mut ours = BoardVec::new();
+        ours.set(template.stone);
+        for &(intrusion, answer) in template.answers.iter() {
+            let mut theirs = BoardVec::new();
+            theirs.set(intrusion);
+            let mut empty = template.carrier;
+            empty.clear(intrusion);
+            patterns[intrusion].push(Pattern {
+                ours: ours,
+                theirs: theirs,
+                empty: empty,
+                to_play: answer,
+                weight: TEMPLATE_WEIGHT,
+            });
+        }
+    }
+    patterns
+}
@@ -0,0 +1,220 @@
+/*!
+ * Allocation of thinking time from a game clock.
+ *
+ * A `Clock` tracks the time remaining to one player under a `TimeControl`, and gives a `Budget`
+ * for each move based on how many moves the player has left to make. `think` then searches
+ * within that budget, taking longer when the best move keeps changing and stopping early once the
+ * best move can no longer be overtaken.
+ */
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use StarAI;
+
+/// Fraction of the remaining time which is never budgeted, to allow for overheads
+const RESERVE: f64 = 0.1;
+/// How many times the target time may be used when the best move is unstable
+const MAX_EXTENSION: f64 = 3.;
+/// The largest fraction of the remaining main time which may be spent on one move
+const MAX_FRACTION: f64 = 0.3;
+/// Fraction of the time spent so far for which the best move must not have changed for it to be
+/// considered stable
+const STABLE_FRACTION: f64 = 0.25;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TimeControl {
+    /// A fixed amount of time for the whole game
+    SuddenDeath { main: Duration },
+    /// `increment` is added to the clock after each move
+    Fischer { main: Duration, increment: Duration },
+    /// Once the main time is used, each move must be made within `period`, with one of `periods`
+    /// periods being lost each time a move takes longer
+    ByoYomi { main: Duration, period: Duration, periods: u32 },
+    /// Once the main time is used, each `stones` moves must be made within `period`
+    Canadian { main: Duration, period: Duration, stones: u32 },
+}
+
+/// Thinking time for a single move
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Budget {
+    /// Time to spend when the best move is stable
+    pub target: Duration,
+    /// Time which should never be exceeded
+    pub maximum: Duration,
+}
+
+impl Budget {
+    /// A budget which always spends the given time
+    pub fn fixed(time: Duration) -> Budget {
+        Budget {
+            target: time,
+            maximum: time,
+        }
+    }
+}
+
+/// The time remaining to one player
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Clock {
+    control: TimeControl,
+    /// Main time remaining
+    main: Duration,
+    /// Time remaining in the current overtime period
+    period: Duration,
+    /// Byo-yomi periods, or moves to be made in the current Canadian period, remaining
+    count: u32,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        let (main, period, count) = match control {
+            TimeControl::SuddenDeath { main } => (main, Duration::from_secs(0), 0),
+            TimeControl::Fischer { main, .. } => (main, Duration::from_secs(0), 0),
+            TimeControl::ByoYomi { main, period, periods } => (main, period, periods),
+            TimeControl::Canadian { main, period, stones } => (main, period, stones),
+        };
+        Clock {
+            control: control,
+            main: main,
+            period: period,
+            count: count,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// Update the clock after a move which took `used`
+    pub fn record_move(&mut self, used: Duration) {
+        let overtime = used.saturating_sub(self.main);
+        self.main = self.main.saturating_sub(used);
+        match self.control {
+            TimeControl::SuddenDeath { .. } => {},
+            TimeControl::Fischer { increment, .. } => {
+                self.main += increment;
+            },
+            TimeControl::ByoYomi { period, .. } => {
+                if overtime > Duration::from_secs(0) {
+                    let lost = (overtime.as_secs_f64() / period.as_secs_f64()) as u32;
+                    self.count = self.count.saturating_sub(lost);
+                }
+            },
+            TimeControl::Canadian { period, stones, .. } => {
+                if overtime > Duration::from_secs(0) || self.main == Duration::from_secs(0) {
+                    self.period = self.period.saturating_sub(overtime);
+                    self.count = self.count.saturating_sub(1);
+                    if self.count == 0 {
+                        self.period = period;
+                        self.count = stones;
+                    }
+                }
+            },
+        }
+    }
+
+    /**
+     * Set the time remaining, as reported by the opponent or a server. If `count` is zero, `time`
+     * is the main time remaining, and otherwise the player is in overtime with `time` left in the
+     * current period and `count` byo-yomi periods or Canadian moves remaining.
+     */
+    pub fn set_remaining(&mut self, time: Duration, count: u32) {
+        if count == 0 {
+            self.main = time;
+        } else {
+            self.main = Duration::from_secs(0);
+            self.period = time;
+            self.count = count;
+        }
+    }
+
+    /// Time which can safely be spent on every move once the main time has been used
+    fn overtime_per_move(&self) -> f64 {
+        if self.count == 0 {
+            return 0.;
+        }
+        match self.control {
+            TimeControl::SuddenDeath { .. } | TimeControl::Fischer { .. } => 0.,
+            TimeControl::ByoYomi { .. } => self.period.as_secs_f64() * (1. - RESERVE),
+            TimeControl::Canadian { .. } => self.period.as_secs_f64() * (1. - RESERVE) / self.count as f64,
+        }
+    }
+
+    /**
+     * The time to spend on the next move when `empty_cells` points of the board are still
+     * unplayed. The main time is spread evenly over the moves remaining to this player, with any
+     * time guaranteed to be available for every move added on top. A Fischer increment is also
+     * added, since it replaces the time used, but it is only credited after the move so the budget
+     * never exceeds the main time.
+     */
+    pub fn budget(&self, empty_cells: usize) -> Budget {
+        let own_moves = cmp::max((empty_cells + 1) / 2, 1) as f64;
+        let main = self.main.as_secs_f64() * (1. - RESERVE);
+        let increment = match self.control {
+            TimeControl::Fischer { increment, .. } => increment.as_secs_f64() * (1. - RESERVE),
+            _ => 0.,
+        };
+        let per_move = self.overtime_per_move();
+        let available = main + per_move;
+        let target = (main / own_moves + increment + per_move).min(available);
+        let maximum = (target * MAX_EXTENSION).min(main * MAX_FRACTION + increment + per_move)
+            .max(target).min(available);
+        Budget {
+            target: Duration::from_secs_f64(target),
+            maximum: Duration::from_secs_f64(maximum),
+        }
+    }
+}
+
+/**
+ * Search the current position of `ai` within the given budget, running `iterations` iterations for
+ * each of its threads between checks of the time, since threads are started for each call to
+ * `calculate` and need enough iterations to be worth starting. Returns the number of iterations
+ * run.
+ *
+ * The search stops at the target time if the best move hasn't changed recently, and otherwise
+ * continues until it has or the maximum time is reached. It stops before the target time if the
+ * best move's lead in visits is greater than the number of visits expected in the time remaining,
+ * or once the result of the game has been proven. The maximum time is exceeded if the current
+ * position hasn't been expanded by then, so that there is a best move to report.
+ */
+pub fn think(ai: &mut StarAI, budget: &Budget, iterations: usize) -> usize {
+    let start_time = Instant::now();
+    let start_visits = ai.progress().map_or(0, |progress| progress.visits);
+    let mut total_iterations = 0;
+    let mut best_move = None;
+    let mut best_move_changed = Duration::from_secs(0);
+    let batch = iterations * ai.threads();
+    loop {
+        ai.calculate(batch);
+        total_iterations += batch;
+        let elapsed = start_time.elapsed();
+        if ai.proven_winner().is_some() {
+            break;
+        }
+        let progress = match ai.progress() {
+            None => continue,
+            Some(progress) => progress,
+        };
+        if elapsed >= budget.maximum {
+            break;
+        }
+        if best_move != Some(progress.best_move) {
+            best_move = Some(progress.best_move);
+            best_move_changed = elapsed;
+        }
+        if elapsed >= budget.target {
+            if (elapsed - best_move_changed).as_secs_f64() >= elapsed.as_secs_f64() * STABLE_FRACTION {
+                break;
+            }
+            continue;
+        }
+        let rate = (progress.visits - start_visits) as f64 / elapsed.as_secs_f64();
+        let remaining = (budget.target - elapsed).as_secs_f64();
+        if progress.lead as f64 > rate * remaining {
+            break;
+        }
+    }
+    total_iterations
+}